@@ -0,0 +1,42 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Path of a new file in the current directory, named after `name` and the current time
+/// so the previous exports aren't overwritten, e.g. `agents-1641823200123.csv`.
+pub fn timestamped_path(name: &str, extension: &str) -> PathBuf {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or_default();
+    PathBuf::from(format!("{}-{}.{}", name, millis, extension))
+}
+
+/// Write `rows` in a CSV file, after the `header` line.
+pub fn write_csv(
+    path: impl AsRef<Path>,
+    header: &[&str],
+    rows: impl IntoIterator<Item = Vec<String>>,
+) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+
+    writeln!(file, "{}", header.join(","))?;
+    for row in rows {
+        writeln!(file, "{}", row.join(","))?;
+    }
+
+    file.flush()
+}
+
+/// Write RGBA `pixels` in a PNG file, by row from the top left corner.
+pub fn write_png(
+    path: impl AsRef<Path>,
+    width: u32,
+    height: u32,
+    pixels: &[u8],
+) -> image::ImageResult<()> {
+    image::save_buffer(path, pixels, width, height, image::ColorType::Rgba8)
+}
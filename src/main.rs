@@ -1,6 +1,8 @@
 #![windows_subsystem = "windows"]
 
+mod export;
 mod simulation;
+mod spatial;
 mod utils;
 mod velocity;
 
@@ -15,6 +17,8 @@ use bevy_egui::{egui, EguiContext, EguiPlugin};
 use bevy_prototype_debug_lines::*;
 
 use simulation::*;
+use utils::DebugLinesExt;
+use velocity::{Integrator, Velocity};
 
 fn main() {
     App::build()
@@ -26,6 +30,9 @@ fn main() {
             ..Default::default()
         })
         .init_resource::<UiState>()
+        .init_resource::<WorldCursor>()
+        .init_resource::<Tools>()
+        .init_resource::<ExportStatus>()
         .add_plugins(DefaultPlugins)
         // // Adds frame time diagnostics
         // .add_plugin(diagnostic::FrameTimeDiagnosticsPlugin::default())
@@ -36,9 +43,21 @@ fn main() {
         .add_plugin(HeroesCowardSimulationPlugin)
         .add_startup_system(setup.system())
         .add_system(ui.system().label("ui"))
+        .add_system(ui_simulation.system().after("ui"))
         .add_system(ui_stats.system().after("ui"))
+        .add_system(ui_agent.system().after("ui"))
+        .add_system(ui_history.system().after("ui"))
+        .add_system(ui_colors.system().after("ui"))
+        .add_system(ui_heatmap.system().after("ui"))
         .add_system(scroll_zoom.system())
         .add_system(move_camera.system())
+        .add_system(track_cursor.system().label("cursor"))
+        .add_system(pick_agent.system().after("cursor"))
+        .add_system(paint_agents.system().after("cursor"))
+        .add_system(select_rectangle.system().after("cursor"))
+        .add_system(link_agents.system().after("cursor"))
+        .add_system(display_tools.system().after("cursor"))
+        .add_system(ui_tools.system().after("ui"))
         .run();
 }
 
@@ -47,6 +66,8 @@ fn main() {
 struct UiState {
     random_seed: bool,
     simulation_settings: SimulationSettings,
+    /// The kind of agent whose settings are edited.
+    kind_tab: AgentBehaviour,
 }
 
 impl Default for UiState {
@@ -54,10 +75,110 @@ impl Default for UiState {
         Self {
             random_seed: true,
             simulation_settings: Default::default(),
+            kind_tab: AgentBehaviour::Heroe,
         }
     }
 }
 
+/// The tool used with the left mouse button.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Tool {
+    /// Select and drag an agent.
+    Select,
+    /// Paint agents under the cursor.
+    Brush,
+    /// Select the agents inside a rectangle.
+    Rectangle,
+    /// Drag from an agent to another to set its friend, or its foe with shift.
+    Link,
+}
+
+/// State of the tools used to edit the agents.
+struct Tools {
+    tool: Tool,
+    /// Kind of the agents painted by the brush.
+    brush_kind: AgentBehaviour,
+    brush_radius: f32,
+    /// Number of agents painted by the brush per 100 × 100 area.
+    brush_density: f32,
+    /// Position of the dragged agent relative to the cursor.
+    drag_offset: Option<Vec3>,
+    /// First corner of the rectangle being drawn.
+    rectangle_start: Option<Vec2>,
+    /// The agents inside the last rectangle.
+    selection: Vec<Entity>,
+    /// The agent whose friend or foe is being set.
+    link_start: Option<Entity>,
+}
+
+impl Default for Tools {
+    fn default() -> Self {
+        Self {
+            tool: Tool::Select,
+            brush_kind: AgentBehaviour::Heroe,
+            brush_radius: 40.0,
+            brush_density: 10.0,
+            drag_offset: None,
+            rectangle_start: None,
+            selection: Vec::new(),
+            link_start: None,
+        }
+    }
+}
+
+/// Result of the last exports, shown next to the export buttons.
+#[derive(Default)]
+struct ExportStatus {
+    agents: Option<String>,
+    heatmap: Option<String>,
+}
+
+/// Message describing the result of an export to `path`.
+fn export_message<E: std::fmt::Display>(path: &std::path::Path, result: Result<(), E>) -> String {
+    match result {
+        Ok(()) => format!("Exporté dans {}", path.display()),
+        Err(err) => format!("Échec de l'export : {}", err),
+    }
+}
+
+/// The mouse cursor in the world, seen through the [`MainCamera`].
+#[derive(Default)]
+struct WorldCursor {
+    /// `None` when the cursor is outside the window or over the UI.
+    position: Option<Vec2>,
+    /// Size of a pixel, in world unit.
+    pixel_size: f32,
+}
+
+impl WorldCursor {
+    /// The closest of the `agents` the cursor is on, with its position.
+    fn agent_under<'a>(
+        &self,
+        simulation_settings: &SimulationSettings,
+        agents: impl Iterator<Item = (Entity, &'a Transform, &'a AgentBehaviour)>,
+    ) -> Option<(Entity, Vec3)> {
+        // minimum distance to pick an agent, in pixels
+        const PICK_DISTANCE: f32 = 6.0;
+
+        let cursor = self.position?.extend(0.0);
+        agents
+            .filter_map(|(entity, transform, behaviour)| {
+                let distance = cursor.distance(transform.translation);
+                let radius = simulation_settings
+                    .kind(behaviour)
+                    .radius
+                    .max(PICK_DISTANCE * self.pixel_size);
+                if distance <= radius {
+                    Some((entity, transform.translation, distance))
+                } else {
+                    None
+                }
+            })
+            .min_by(|(.., a), (.., b)| a.total_cmp(b))
+            .map(|(entity, position, _)| (entity, position))
+    }
+}
+
 // ===== components =====
 
 /// Tag for the camera
@@ -76,10 +197,7 @@ fn ui(
     mut commands: Commands,
     egui_context: ResMut<EguiContext>,
     mut ui_state: ResMut<UiState>,
-    mut simulation_speed: ResMut<SimulationSpeed>,
     mut simulation_state: ResMut<State<SimulationState>>,
-    mut debug_settings: ResMut<SimulationDebug>,
-    simulation_settings: Res<SimulationSettings>,
 ) {
     egui::Window::new("Paramètres").show(egui_context.ctx(), |ui| {
         ui.vertical_centered_justified(|ui| {
@@ -139,34 +257,282 @@ fn ui(
                 ));
                 ui.end_row();
 
-                ui.label("Vision limitée");
+                ui.label("Champ de vision");
                 ui.scope(|ui| {
-                    ui.checkbox(&mut ui_state.simulation_settings.use_vision_limit, "");
-                    ui.set_enabled(ui_state.simulation_settings.use_vision_limit);
-                    let vision_max = ui_state.simulation_settings.arena_size;
-                    ui.add(egui::Slider::new(
-                        &mut ui_state.simulation_settings.vision_limit,
-                        0.0..=vision_max,
-                    ));
+                    ui.checkbox(&mut ui_state.simulation_settings.use_field_of_view, "");
+                    ui.set_enabled(ui_state.simulation_settings.use_field_of_view);
+                    ui.add(
+                        egui::Slider::new(
+                            &mut ui_state.simulation_settings.field_of_view,
+                            0.0..=360.0,
+                        )
+                        .suffix("°"),
+                    );
                 });
                 ui.end_row();
 
-                ui.label("Comportement si aveugle");
+                ui.label("Occlusion");
+                ui.checkbox(&mut ui_state.simulation_settings.use_occlusion, "");
+                ui.end_row();
+
+                ui.label("Collisions");
+                ui.horizontal(|ui| {
+                    ui.selectable_value(
+                        &mut ui_state.simulation_settings.collision,
+                        CollisionMode::None,
+                        "Aucune",
+                    );
+                    ui.selectable_value(
+                        &mut ui_state.simulation_settings.collision,
+                        CollisionMode::Separation,
+                        "Séparation",
+                    );
+                    ui.selectable_value(
+                        &mut ui_state.simulation_settings.collision,
+                        CollisionMode::Resolve,
+                        "Résolution",
+                    );
+                });
                 ui.end_row();
-                ui.vertical_centered_justified(|ui| {
+
+                ui.label("Mise à jour");
+                ui.horizontal(|ui| {
+                    ui.selectable_value(
+                        &mut ui_state.simulation_settings.update_scheme,
+                        UpdateScheme::Synchronous,
+                        "Synchrone",
+                    );
                     ui.selectable_value(
-                        &mut ui_state.simulation_settings.blind_behaviour,
-                        BlindBehavour::NoMove,
-                        "Immobile",
+                        &mut ui_state.simulation_settings.update_scheme,
+                        UpdateScheme::RandomSequential,
+                        "Séquentielle aléatoire",
                     );
                     ui.selectable_value(
-                        &mut ui_state.simulation_settings.blind_behaviour,
-                        BlindBehavour::RandomMove,
-                        "Movement aléatoire",
+                        &mut ui_state.simulation_settings.update_scheme,
+                        UpdateScheme::FixedSequential,
+                        "Séquentielle fixe",
+                    );
+                });
+                ui.end_row();
+
+                ui.label("Durée de la mémoire");
+                ui.add(
+                    egui::DragValue::new(&mut ui_state.simulation_settings.memory_duration)
+                        .clamp_range(0.0..=60.0)
+                        .suffix(" s"),
+                );
+                ui.end_row();
+
+                ui.label("Errance");
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut ui_state.simulation_settings.wander_distance)
+                            .clamp_range(0.0..=10.0)
+                            .speed(0.1)
+                            .prefix("distance "),
+                    );
+                    ui.add(
+                        egui::DragValue::new(&mut ui_state.simulation_settings.wander_radius)
+                            .clamp_range(0.1..=10.0)
+                            .speed(0.1)
+                            .prefix("rayon "),
+                    );
+                    ui.add(
+                        egui::DragValue::new(&mut ui_state.simulation_settings.wander_jitter)
+                            .clamp_range(0.0..=50.0)
+                            .speed(0.1)
+                            .prefix("bruit "),
+                    );
+                });
+                ui.end_row();
+
+                ui.label("Écart de la spirale");
+                ui.add(
+                    egui::DragValue::new(&mut ui_state.simulation_settings.spiral_spacing)
+                        .clamp_range(1.0..=500.0),
+                );
+                ui.end_row();
+
+                ui.label("Modèle");
+                ui.vertical(|ui| {
+                    ui.horizontal(|ui| {
+                        ui.selectable_value(
+                            &mut ui_state.simulation_settings.movement_model,
+                            MovementModel::VectorSum,
+                            "Somme des vecteurs",
+                        );
+                        ui.selectable_value(
+                            &mut ui_state.simulation_settings.movement_model,
+                            MovementModel::TargetPoint,
+                            "Point cible",
+                        );
+                    });
+                    ui.set_enabled(
+                        ui_state.simulation_settings.movement_model == MovementModel::TargetPoint,
+                    );
+                    ui.add(
+                        egui::Slider::new(
+                            &mut ui_state.simulation_settings.heroe_fraction,
+                            0.0..=1.0,
+                        )
+                        .text("position des héros"),
+                    );
+                    ui.add(
+                        egui::DragValue::new(&mut ui_state.simulation_settings.hiding_distance)
+                            .clamp_range(0.0..=500.0)
+                            .prefix("distance de cachette "),
+                    );
+                });
+                ui.end_row();
+
+                ui.label("Réponse à la distance");
+                ui.vertical(|ui| {
+                    ui.horizontal(|ui| {
+                        ui.selectable_value(
+                            &mut ui_state.simulation_settings.distance_response,
+                            DistanceResponse::Raw,
+                            "Brute",
+                        );
+                        ui.selectable_value(
+                            &mut ui_state.simulation_settings.distance_response,
+                            DistanceResponse::Unit,
+                            "Unitaire",
+                        );
+                        ui.selectable_value(
+                            &mut ui_state.simulation_settings.distance_response,
+                            DistanceResponse::InverseDistance,
+                            "Inverse",
+                        );
+                        ui.selectable_value(
+                            &mut ui_state.simulation_settings.distance_response,
+                            DistanceResponse::Gaussian,
+                            "Gaussienne",
+                        );
+                    });
+                    ui.add(
+                        egui::DragValue::new(&mut ui_state.simulation_settings.response_scale)
+                            .clamp_range(1.0..=1000.0)
+                            .prefix("échelle "),
+                    );
+                });
+                ui.end_row();
+
+                ui.label("Anticipation");
+                ui.scope(|ui| {
+                    ui.checkbox(&mut ui_state.simulation_settings.use_anticipation, "");
+                    ui.set_enabled(ui_state.simulation_settings.use_anticipation);
+                    ui.add(
+                        egui::DragValue::new(
+                            &mut ui_state.simulation_settings.anticipation_horizon,
+                        )
+                        .clamp_range(0.0..=10.0)
+                        .speed(0.01)
+                        .suffix(" s"),
+                    );
+                });
+                ui.end_row();
+
+                ui.label("Perception");
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut ui_state.simulation_settings.perception_noise)
+                            .clamp_range(0.0..=100.0)
+                            .speed(0.1)
+                            .prefix("bruit "),
+                    );
+                    ui.add(
+                        egui::DragValue::new(&mut ui_state.simulation_settings.perception_delay)
+                            .clamp_range(0..=120)
+                            .prefix("délai ")
+                            .suffix(" ticks"),
+                    );
+                });
+                ui.end_row();
+
+                ui.label("Inertie");
+                ui.vertical(|ui| {
+                    ui.checkbox(&mut ui_state.simulation_settings.use_steering, "");
+                    ui.set_enabled(ui_state.simulation_settings.use_steering);
+                    let steering = &mut ui_state.simulation_settings.steering;
+                    ui.add(
+                        egui::DragValue::new(&mut steering.mass)
+                            .clamp_range(0.1..=10.0)
+                            .speed(0.1)
+                            .prefix("masse "),
+                    );
+                    ui.add(
+                        egui::DragValue::new(&mut steering.max_force)
+                            .clamp_range(0.1..=50.0)
+                            .speed(0.1)
+                            .prefix("force max "),
+                    );
+                    ui.add(
+                        egui::DragValue::new(&mut steering.max_speed)
+                            .clamp_range(0.1..=10.0)
+                            .speed(0.1)
+                            .prefix("vitesse max "),
+                    );
+                    ui.add(
+                        egui::DragValue::new(&mut steering.drag)
+                            .clamp_range(0.0..=10.0)
+                            .speed(0.1)
+                            .prefix("frottement "),
+                    )
+                    .on_hover_text("La vitesse limite est vitesse / (1 + masse × frottement)");
+                    ui.add(
+                        egui::DragValue::new(&mut steering.arrive_radius)
+                            .clamp_range(0.0..=500.0)
+                            .prefix("rayon d'arrivée "),
+                    );
+                });
+                ui.end_row();
+
+                ui.label("Intégration");
+                ui.horizontal(|ui| {
+                    let settings = &mut ui_state.simulation_settings;
+                    if settings.use_steering {
+                        for (integrator, name) in [
+                            (Integrator::ExplicitEuler, "Euler"),
+                            (Integrator::SemiImplicitEuler, "Euler semi-implicite"),
+                            (Integrator::Verlet, "Verlet"),
+                            (Integrator::RungeKutta4, "RK4"),
+                        ] {
+                            ui.selectable_value(&mut settings.integrator, integrator, name);
+                        }
+                    } else {
+                        // without acceleration, the methods are first order ones
+                        for (integrator, name) in [
+                            (Integrator::ExplicitEuler, "Euler"),
+                            (Integrator::Verlet, "Heun"),
+                            (Integrator::RungeKutta4, "RK4"),
+                        ] {
+                            let selected = settings.integrator.first_order() == integrator;
+                            if ui.selectable_label(selected, name).clicked() {
+                                settings.integrator = integrator;
+                            }
+                        }
+                    }
+                    ui.add(
+                        egui::DragValue::new(&mut settings.sub_steps)
+                            .clamp_range(1..=64)
+                            .suffix(" sous-pas"),
                     );
                 });
                 ui.end_row();
             });
+
+            ui.add_space(10.0);
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut ui_state.kind_tab, AgentBehaviour::Heroe, "Héros");
+                ui.selectable_value(&mut ui_state.kind_tab, AgentBehaviour::Coward, "Lâches");
+            });
+            let collision = ui_state.simulation_settings.collision.clone();
+            let kind_settings = match ui_state.kind_tab {
+                AgentBehaviour::Heroe => &mut ui_state.simulation_settings.heroe,
+                AgentBehaviour::Coward => &mut ui_state.simulation_settings.coward,
+            };
+            kind_settings_ui(ui, kind_settings, &collision);
+
             ui.add_space(20.0);
             if ui.button("Start").clicked() {
                 if ui_state.random_seed {
@@ -180,7 +546,16 @@ fn ui(
             }
         });
     });
+}
 
+fn ui_simulation(
+    egui_context: ResMut<EguiContext>,
+    mut simulation_speed: ResMut<SimulationSpeed>,
+    mut temperature: ResMut<SimulationTemperature>,
+    mut simulation_state: ResMut<State<SimulationState>>,
+    mut debug_settings: ResMut<SimulationDebug>,
+    simulation_settings: Res<SimulationSettings>,
+) {
     egui::Window::new("Simulation").show(egui_context.ctx(), |ui| {
         ui.vertical_centered_justified(|ui| {
             egui::Grid::new("grid_sim").show(ui, |ui| {
@@ -191,6 +566,10 @@ fn ui(
                 ui.label("Vitesse");
                 ui.add(egui::Slider::new(&mut simulation_speed.0, 1.0..=1000.0));
                 ui.end_row();
+
+                ui.label("Température");
+                ui.add(egui::Slider::new(&mut temperature.0, 0.0..=2.0).logarithmic(true));
+                ui.end_row();
             });
 
             ui.vertical(|ui| {
@@ -207,6 +586,46 @@ fn ui(
                     "Afficher le centre de masse ?",
                 );
                 ui.checkbox(&mut debug_settings.deviation, "Afficher la deviation ?");
+                ui.checkbox(
+                    &mut debug_settings.hulls,
+                    "Afficher les enveloppes convexes ?",
+                );
+                ui.checkbox(
+                    &mut debug_settings.vision_cone,
+                    "Afficher le cône de vision ?",
+                );
+                ui.checkbox(
+                    &mut debug_settings.arena_bounds,
+                    "Afficher les limites de l'arène ?",
+                );
+                ui.checkbox(&mut debug_settings.trails, "Afficher les traînées ?");
+                ui.checkbox(
+                    &mut debug_settings.selected_trail,
+                    "Afficher la traînée de l'agent sélectionné ?",
+                );
+                ui.add(
+                    egui::Slider::new(&mut debug_settings.trail_length, 2..=500)
+                        .text("Longueur des traînées"),
+                );
+                ui.checkbox(
+                    &mut debug_settings.heatmap,
+                    "Afficher la carte de densité ?",
+                );
+            });
+
+            egui::Grid::new("grid_overlays").show(ui, |ui| {
+                let debug_settings = &mut *debug_settings;
+                for (name, scope) in [
+                    ("Points cibles", &mut debug_settings.target_points),
+                    ("Vélocités", &mut debug_settings.velocities),
+                    ("Portées de vision", &mut debug_settings.vision_ranges),
+                ] {
+                    ui.label(name);
+                    ui.selectable_value(scope, OverlayScope::Hidden, "Aucun");
+                    ui.selectable_value(scope, OverlayScope::Selected, "Sélection");
+                    ui.selectable_value(scope, OverlayScope::All, "Tous");
+                    ui.end_row();
+                }
             });
 
             ui.add_space(20.0);
@@ -232,7 +651,411 @@ fn ui(
     });
 }
 
-fn ui_stats(egui_context: ResMut<EguiContext>, stats: Res<SimStats>) {
+/// Window to choose what the [`Heatmap`] displays and export it, shown with the heatmap.
+fn ui_heatmap(
+    egui_context: ResMut<EguiContext>,
+    mut debug_settings: ResMut<SimulationDebug>,
+    mut heatmap: ResMut<Heatmap>,
+    simulation_settings: Res<SimulationSettings>,
+    mut export_status: ResMut<ExportStatus>,
+) {
+    if !debug_settings.heatmap {
+        return;
+    }
+
+    egui::Window::new("Carte de densité").show(egui_context.ctx(), |ui| {
+        ui.horizontal(|ui| {
+            ui.selectable_value(
+                &mut debug_settings.heatmap_mode,
+                HeatmapMode::Instantaneous,
+                "Instantanée",
+            );
+            ui.selectable_value(
+                &mut debug_settings.heatmap_mode,
+                HeatmapMode::Integrated,
+                "Cumulée",
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut debug_settings.heatmap_kind, None, "Tous");
+            ui.selectable_value(
+                &mut debug_settings.heatmap_kind,
+                Some(AgentBehaviour::Heroe),
+                "Héros",
+            );
+            ui.selectable_value(
+                &mut debug_settings.heatmap_kind,
+                Some(AgentBehaviour::Coward),
+                "Lâches",
+            );
+        });
+
+        ui.horizontal(|ui| {
+            if ui.button("Réinitialiser").clicked() {
+                *heatmap = Heatmap::default();
+            }
+            if ui.button("Exporter (CSV)").clicked() {
+                let mode = debug_settings.heatmap_mode;
+                let cell_size = 2.0 * simulation_settings.arena_size / HEATMAP_RESOLUTION as f32;
+                let heroes = heatmap.values(mode, Some(AgentBehaviour::Heroe));
+                let cowards = heatmap.values(mode, Some(AgentBehaviour::Coward));
+                let rows =
+                    heroes
+                        .iter()
+                        .zip(&cowards)
+                        .enumerate()
+                        .map(|(cell, (heroes, cowards))| {
+                            let (column, row) =
+                                (cell % HEATMAP_RESOLUTION, cell / HEATMAP_RESOLUTION);
+                            vec![
+                                column.to_string(),
+                                row.to_string(),
+                                ((column as f32 + 0.5) * cell_size
+                                    - simulation_settings.arena_size)
+                                    .to_string(),
+                                ((row as f32 + 0.5) * cell_size - simulation_settings.arena_size)
+                                    .to_string(),
+                                heroes.to_string(),
+                                cowards.to_string(),
+                            ]
+                        });
+                let path = export::timestamped_path("heatmap", "csv");
+                let result = export::write_csv(
+                    &path,
+                    &["column", "row", "x", "y", "heroes", "cowards"],
+                    rows,
+                );
+                export_status.heatmap = Some(export_message(&path, result));
+            }
+            if ui.button("Exporter (PNG)").clicked() {
+                let pixels =
+                    heatmap.image(debug_settings.heatmap_mode, debug_settings.heatmap_kind);
+                let size = HEATMAP_RESOLUTION as u32;
+                let path = export::timestamped_path("heatmap", "png");
+                let result = export::write_png(&path, size, size, &pixels);
+                export_status.heatmap = Some(export_message(&path, result));
+            }
+        });
+        if let Some(status) = &export_status.heatmap {
+            ui.label(status);
+        }
+    });
+}
+
+fn ui_colors(
+    egui_context: ResMut<EguiContext>,
+    mut debug_settings: ResMut<SimulationDebug>,
+    legend: Res<ColorLegend>,
+) {
+    egui::Window::new("Couleurs").show(egui_context.ctx(), |ui| {
+        for (mode, name) in [
+            (ColorMode::Kind, "Type"),
+            (ColorMode::Speed, "Vitesse"),
+            (ColorMode::FoeDistance, "Distance à l'ennemi"),
+            (ColorMode::Cluster, "Groupe"),
+            (ColorMode::Visibility, "Visibilité"),
+            (ColorMode::FoeInDegree, "Ennemi de"),
+        ] {
+            ui.radio_value(&mut debug_settings.color_mode, mode, name);
+        }
+        ui.separator();
+
+        // legend
+        let swatch = |ui: &mut egui::Ui, color: Color, text: &str| {
+            ui.horizontal(|ui| {
+                ui.colored_label(egui_color(color), "■");
+                ui.label(text);
+            });
+        };
+        let gradient = |ui: &mut egui::Ui, max: String| {
+            ui.horizontal(|ui| {
+                ui.label("0");
+                for step in 0..=4 {
+                    ui.colored_label(egui_color(gradient_color(step as f32 / 4.0)), "■");
+                }
+                ui.label(max);
+            });
+        };
+        match debug_settings.color_mode {
+            ColorMode::Kind => {
+                swatch(ui, kind_color(&AgentBehaviour::Heroe), "Héros");
+                swatch(ui, kind_color(&AgentBehaviour::Coward), "Lâches");
+            }
+            ColorMode::Speed => gradient(ui, format!("{:.1}", legend.max)),
+            ColorMode::FoeDistance => {
+                gradient(ui, format!("{:.0}", legend.max));
+                swatch(ui, NEUTRAL_COLOR, "Sans ennemi");
+            }
+            ColorMode::Cluster => {
+                ui.add(
+                    egui::Slider::new(&mut debug_settings.cluster_distance, 1.0..=200.0)
+                        .text("Distance"),
+                );
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} groupes", legend.clusters));
+                    for color in CATEGORY_COLORS {
+                        ui.colored_label(egui_color(color), "■");
+                    }
+                });
+                swatch(ui, NEUTRAL_COLOR, "Isolé");
+            }
+            ColorMode::Visibility => {
+                for (category, text) in [
+                    "Ne voit personne",
+                    "Voit son ami",
+                    "Voit son ennemi",
+                    "Voit les deux",
+                ]
+                .iter()
+                .enumerate()
+                {
+                    swatch(ui, CATEGORY_COLORS[category], text);
+                }
+            }
+            ColorMode::FoeInDegree => gradient(ui, format!("{:.0} agents", legend.max)),
+        }
+    });
+}
+
+/// Convert a color to be displayed by egui.
+fn egui_color(color: Color) -> egui::Color32 {
+    let [r, g, b, _] = color.as_rgba_f32();
+    egui::Color32::from_rgb((r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8)
+}
+
+fn ui_history(
+    egui_context: ResMut<EguiContext>,
+    simulation_state: Res<State<SimulationState>>,
+    mut history: ResMut<SimulationHistory>,
+    mut step: ResMut<SimulationStep>,
+) {
+    egui::Window::new("Historique").show(egui_context.ctx(), |ui| {
+        // the history can only be browsed while the simulation is paused
+        ui.set_enabled(*simulation_state.current() == SimulationState::Pause);
+
+        let current = history.current();
+        ui.horizontal(|ui| {
+            if ui.button("◀").clicked() {
+                if let Some(index) = current.and_then(|index| index.checked_sub(1)) {
+                    history.restore(index);
+                }
+            }
+            if ui.button("▶").clicked() {
+                if let Some(index) = current.filter(|index| index + 1 < history.len()) {
+                    history.restore(index + 1);
+                }
+            }
+            if ui.button("Pas").clicked() {
+                step.0 = true;
+            }
+        });
+
+        if let Some(mut index) = current {
+            let tick = history.tick(index).unwrap_or_default();
+            let response = ui.add(
+                egui::Slider::new(&mut index, 0..=history.len() - 1)
+                    .show_value(false)
+                    .text(format!("tick {}", tick)),
+            );
+            if response.changed() {
+                history.restore(index);
+            }
+        } else if history.is_empty() {
+            ui.label("Aucun état enregistré");
+        }
+    });
+}
+
+/// Widgets to edit the [`KindSettings`] of a kind of agent.
+fn kind_settings_ui(ui: &mut egui::Ui, settings: &mut KindSettings, collision: &CollisionMode) {
+    egui::Grid::new("grid_kind").show(ui, |ui| {
+        ui.label("Vitesse");
+        distribution_ui(ui, &mut settings.speed, 0.01);
+        ui.end_row();
+
+        ui.label("Multiplicateur de vitesse");
+        ui.add(
+            egui::DragValue::new(&mut settings.speed_multiplier)
+                .clamp_range(0.0..=10.0)
+                .speed(0.01),
+        );
+        ui.end_row();
+
+        ui.label("Vision limitée");
+        ui.checkbox(&mut settings.use_vision_limit, "");
+        ui.end_row();
+
+        ui.label("Vision");
+        ui.scope(|ui| {
+            ui.set_enabled(settings.use_vision_limit);
+            distribution_ui(ui, &mut settings.vision, 1.0);
+        });
+        ui.end_row();
+
+        ui.label("Poids");
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut settings.friend_weight)
+                    .clamp_range(0.0..=10.0)
+                    .speed(0.01)
+                    .prefix("ami "),
+            );
+            ui.add(
+                egui::DragValue::new(&mut settings.foe_weight)
+                    .clamp_range(0.0..=10.0)
+                    .speed(0.01)
+                    .prefix("ennemi "),
+            );
+        });
+        ui.end_row();
+
+        ui.label("Rayon");
+        ui.add(egui::DragValue::new(&mut settings.radius).clamp_range(0.5..=50.0));
+        ui.end_row();
+
+        ui.label("Force de séparation");
+        ui.scope(|ui| {
+            ui.set_enabled(*collision == CollisionMode::Separation);
+            ui.add(
+                egui::DragValue::new(&mut settings.separation)
+                    .clamp_range(0.0..=10.0)
+                    .speed(0.1),
+            );
+        });
+        ui.end_row();
+
+        ui.label("Comportement si aveugle");
+        ui.end_row();
+        ui.vertical_centered_justified(|ui| {
+            ui.selectable_value(
+                &mut settings.blind_behaviour,
+                BlindBehavour::NoMove,
+                "Immobile",
+            );
+            ui.selectable_value(
+                &mut settings.blind_behaviour,
+                BlindBehavour::RandomMove,
+                "Movement aléatoire",
+            );
+            ui.selectable_value(
+                &mut settings.blind_behaviour,
+                BlindBehavour::Memory,
+                "Mémoire",
+            );
+            ui.selectable_value(
+                &mut settings.blind_behaviour,
+                BlindBehavour::MemoryThenRandom,
+                "Mémoire puis aléatoire",
+            );
+            ui.selectable_value(
+                &mut settings.blind_behaviour,
+                BlindBehavour::Wander,
+                "Errance",
+            );
+            ui.selectable_value(
+                &mut settings.blind_behaviour,
+                BlindBehavour::ReturnToCenter,
+                "Retour au centre",
+            );
+            ui.selectable_value(
+                &mut settings.blind_behaviour,
+                BlindBehavour::FollowNearest,
+                "Suivre le plus proche",
+            );
+            ui.selectable_value(
+                &mut settings.blind_behaviour,
+                BlindBehavour::SpiralSearch,
+                "Recherche en spirale",
+            );
+        });
+        ui.end_row();
+    });
+}
+
+/// Widgets to edit a [`ParamDistribution`], `speed` is the drag speed of the values.
+fn distribution_ui(ui: &mut egui::Ui, distribution: &mut ParamDistribution, speed: f32) {
+    ui.vertical(|ui| {
+        ui.horizontal(|ui| {
+            let mean = distribution.mean();
+            if ui
+                .selectable_label(
+                    matches!(distribution, ParamDistribution::Constant(_)),
+                    "constante",
+                )
+                .clicked()
+            {
+                *distribution = ParamDistribution::Constant(mean);
+            }
+            if ui
+                .selectable_label(
+                    matches!(distribution, ParamDistribution::Uniform(..)),
+                    "uniforme",
+                )
+                .clicked()
+            {
+                *distribution = ParamDistribution::Uniform(0.5 * mean, 1.5 * mean);
+            }
+            if ui
+                .selectable_label(
+                    matches!(distribution, ParamDistribution::Normal { .. }),
+                    "normale",
+                )
+                .clicked()
+            {
+                *distribution = ParamDistribution::Normal {
+                    mean,
+                    std_dev: 0.1 * mean,
+                };
+            }
+        });
+
+        ui.horizontal(|ui| match distribution {
+            ParamDistribution::Constant(value) => {
+                ui.add(
+                    egui::DragValue::new(value)
+                        .clamp_range(0.0..=10000.0)
+                        .speed(speed),
+                );
+            }
+            ParamDistribution::Uniform(min, max) => {
+                ui.add(
+                    egui::DragValue::new(min)
+                        .clamp_range(0.0..=10000.0)
+                        .speed(speed)
+                        .prefix("min "),
+                );
+                ui.add(
+                    egui::DragValue::new(max)
+                        .clamp_range(0.0..=10000.0)
+                        .speed(speed)
+                        .prefix("max "),
+                );
+            }
+            ParamDistribution::Normal { mean, std_dev } => {
+                ui.add(
+                    egui::DragValue::new(mean)
+                        .clamp_range(0.0..=10000.0)
+                        .speed(speed)
+                        .prefix("moyenne "),
+                );
+                ui.add(
+                    egui::DragValue::new(std_dev)
+                        .clamp_range(0.0..=10000.0)
+                        .speed(speed)
+                        .prefix("écart-type "),
+                );
+            }
+        });
+    });
+}
+
+fn ui_stats(
+    egui_context: ResMut<EguiContext>,
+    stats: Res<SimStats>,
+    agents: Option<Res<Agents>>,
+    agent_query: Query<(&Transform, &Velocity, &AgentBehaviour, &Speed, &VisionRange)>,
+    mut export_status: ResMut<ExportStatus>,
+) {
     egui::Window::new("Stats").show(egui_context.ctx(), |ui| {
         egui::Grid::new("grid_stats").show(ui, |ui| {
             ui.label("Centre de masse");
@@ -245,7 +1068,217 @@ fn ui_stats(egui_context: ResMut<EguiContext>, stats: Res<SimStats>) {
             ui.label("Déviation");
             ui.label(format!("{:.4}", stats.deviation));
             ui.end_row();
+
+            ui.label("Déviation moyenne (5 s)");
+            ui.label(format!("{:.4}", stats.mean_deviation));
+            ui.end_row();
+
+            for (name, hull) in [
+                ("Enveloppe des héros", &stats.heroe_hull),
+                ("Enveloppe des lâches", &stats.coward_hull),
+                ("Enveloppe de tous", &stats.hull),
+            ] {
+                ui.label(name);
+                ui.label(format!(
+                    "aire {:.0} - périmètre {:.0}",
+                    hull.area, hull.perimeter
+                ));
+                ui.end_row();
+            }
+
+            ui.label("Écart dû au bruit");
+            ui.label(format!("{:.2}", stats.noise_shift));
+            ui.end_row();
+
+            ui.label("Énergie cinétique");
+            ui.label(format!("{:.0}", stats.kinetic_energy));
+            ui.end_row();
+
+            ui.label("Dépassement");
+            ui.label(format!("{:.1} %", 100.0 * stats.overshoot));
+            ui.end_row();
+
+            ui.label("Chevauchement");
+            ui.label(format!("{:.1} %", 100.0 * stats.overlap));
+            ui.end_row();
+
+            ui.label("Vitesse moyenne");
+            ui.label(format!(
+                "héros {:.2} - lâches {:.2}",
+                stats.mean_speed.0, stats.mean_speed.1
+            ));
+            ui.end_row();
+
+            ui.label("Vision moyenne");
+            ui.label(format!(
+                "héros {:.1} - lâches {:.1}",
+                stats.mean_vision.0, stats.mean_vision.1
+            ));
+            ui.end_row();
+        });
+
+        if let Some(agents) = agents {
+            if ui.button("Exporter les agents (CSV)").clicked() {
+                let rows = agents.0.iter().enumerate().filter_map(|(index, entity)| {
+                    let (
+                        transform,
+                        Velocity(velocity),
+                        behaviour,
+                        Speed(speed),
+                        VisionRange(vision),
+                    ) = agent_query.get(*entity).ok()?;
+                    Some(vec![
+                        index.to_string(),
+                        format!("{:?}", behaviour),
+                        transform.translation.x.to_string(),
+                        transform.translation.y.to_string(),
+                        velocity.x.to_string(),
+                        velocity.y.to_string(),
+                        speed.to_string(),
+                        vision.to_string(),
+                    ])
+                });
+                let path = export::timestamped_path("agents", "csv");
+                let result = export::write_csv(
+                    &path,
+                    &["index", "kind", "x", "y", "vx", "vy", "speed", "vision"],
+                    rows,
+                );
+                export_status.agents = Some(export_message(&path, result));
+            }
+            if let Some(status) = &export_status.agents {
+                ui.label(status);
+            }
+        }
+    });
+}
+
+fn ui_agent(
+    egui_context: ResMut<EguiContext>,
+    agents: Option<Res<Agents>>,
+    mut selected_agent: ResMut<SelectedAgent>,
+    agent_query: Query<(&Transform, &Velocity, &AgentBehaviour, &Speed, &VisionRange)>,
+    relation_query: Query<(Option<&FriendFoe>, &Sight, &Target, Option<&Pinned>)>,
+    mut edits: EventWriter<EditAgent>,
+) {
+    let agents = match agents {
+        Some(agents) if !agents.0.is_empty() => agents,
+        _ => return,
+    };
+
+    egui::Window::new("Agent").show(egui_context.ctx(), |ui| {
+        let index_of = |entity: Entity| agents.0.iter().position(|e| *e == entity);
+        let index = selected_agent.0.and_then(index_of);
+
+        ui.horizontal(|ui| {
+            if ui.button("◀").clicked() {
+                let index = index.map_or(0, |i| (i + agents.0.len() - 1) % agents.0.len());
+                selected_agent.0 = Some(agents.0[index]);
+            }
+            match index {
+                Some(index) => ui.label(format!("n°{}", index)),
+                None => ui.label("aucun"),
+            };
+            if ui.button("▶").clicked() {
+                let index = index.map_or(0, |i| (i + 1) % agents.0.len());
+                selected_agent.0 = Some(agents.0[index]);
+            }
         });
+
+        let selected = selected_agent
+            .0
+            .and_then(|e| Some((e, agent_query.get(e).ok()?, relation_query.get(e).ok()?)));
+        if let Some((
+            entity,
+            (transform, Velocity(velocity), behaviour, Speed(speed), VisionRange(vision)),
+            (friend_foe, sight, Target(target), pinned),
+        )) = selected
+        {
+            let position = transform.translation;
+            let (friend, foe) = match friend_foe {
+                Some(FriendFoe(friend, foe)) => (Some(*friend), Some(*foe)),
+                None => (None, None),
+            };
+            let relation = |entity: Option<Entity>| match entity
+                .and_then(|entity| Some((index_of(entity)?, agent_query.get(entity).ok()?)))
+            {
+                Some((index, (other, ..))) => {
+                    format!("n°{} à {:.1}", index, position.distance(other.translation))
+                }
+                _ => "aucun".to_string(),
+            };
+            let yes_no = |value: bool| if value { "oui" } else { "non" };
+
+            egui::Grid::new("grid_agent").show(ui, |ui| {
+                ui.label("Type");
+                ui.label(match behaviour {
+                    AgentBehaviour::Heroe => "héros",
+                    AgentBehaviour::Coward => "lâche",
+                });
+                ui.end_row();
+
+                ui.label("Position");
+                ui.label(format!("{:.1} - {:.1}", position.x, position.y));
+                ui.end_row();
+
+                ui.label("Vélocité");
+                ui.label(format!("{:.1} - {:.1}", velocity.x, velocity.y));
+                ui.end_row();
+
+                ui.label("Vitesse");
+                ui.label(format!("{:.2}", speed));
+                ui.end_row();
+
+                ui.label("Vision");
+                ui.label(format!("{:.1}", vision));
+                ui.end_row();
+
+                ui.label("Ami");
+                ui.label(relation(friend));
+                ui.end_row();
+
+                ui.label("Ennemi");
+                ui.label(relation(foe));
+                ui.end_row();
+
+                ui.label("Voit l'ami");
+                ui.label(yes_no(sight.friend));
+                ui.end_row();
+
+                ui.label("Voit l'ennemi");
+                ui.label(yes_no(sight.foe));
+                ui.end_row();
+
+                ui.label("Cible");
+                ui.label(match target {
+                    Some(target) => format!("{:.1} - {:.1}", target.x, target.y),
+                    None => "aucune".to_string(),
+                });
+                ui.end_row();
+            });
+
+            ui.horizontal(|ui| {
+                let pin_label = if pinned.is_some() {
+                    "Libérer"
+                } else {
+                    "Épingler"
+                };
+                if ui.button(pin_label).clicked() {
+                    edits.send(EditAgent::SetPinned(entity, pinned.is_none()));
+                }
+                if ui.button("Changer de type").clicked() {
+                    let kind = match behaviour {
+                        AgentBehaviour::Heroe => AgentBehaviour::Coward,
+                        AgentBehaviour::Coward => AgentBehaviour::Heroe,
+                    };
+                    edits.send(EditAgent::SetKind(entity, kind));
+                }
+                if ui.button("Supprimer").clicked() {
+                    edits.send(EditAgent::Delete(entity));
+                    selected_agent.0 = None;
+                }
+            });
+        }
     });
 }
 
@@ -291,3 +1324,275 @@ fn move_camera(
         }
     }
 }
+
+/// Update the [`WorldCursor`].
+fn track_cursor(
+    windows: Res<Windows>,
+    egui_context: ResMut<EguiContext>,
+    mut cursor: ResMut<WorldCursor>,
+    camera: Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
+) {
+    let (camera, projection) = camera.single().unwrap();
+    let ctx = egui_context.ctx();
+
+    cursor.pixel_size = projection.scale;
+    cursor.position = match windows.get_primary() {
+        Some(window) if !ctx.wants_pointer_input() && !ctx.is_pointer_over_area() => {
+            window.cursor_position().map(|position| {
+                let size = Vec2::new(window.width(), window.height());
+                // the projection is centered on the camera and scaled by the zoom
+                camera.translation.truncate() + (position - size / 2.0) * projection.scale
+            })
+        }
+        _ => None,
+    };
+}
+
+/// Select the agent under the cursor on left click,
+/// and drag it while the button is pressed.
+fn pick_agent(
+    buttons: Res<Input<MouseButton>>,
+    cursor: Res<WorldCursor>,
+    simulation_settings: Res<SimulationSettings>,
+    mut selected_agent: ResMut<SelectedAgent>,
+    mut tools: ResMut<Tools>,
+    mut edits: EventWriter<EditAgent>,
+    agents: Query<(Entity, &Transform, &AgentBehaviour), With<Agent>>,
+) {
+    if buttons.just_released(MouseButton::Left) || tools.tool != Tool::Select {
+        tools.drag_offset = None;
+        return;
+    }
+    let cursor_position = match cursor.position {
+        Some(position) => position.extend(0.0),
+        None => return,
+    };
+
+    if buttons.just_pressed(MouseButton::Left) {
+        let picked = cursor.agent_under(&simulation_settings, agents.iter());
+        selected_agent.0 = picked.map(|(entity, _)| entity);
+        tools.drag_offset = picked.map(|(_, position)| position - cursor_position);
+    } else if buttons.pressed(MouseButton::Left) {
+        if let (Some(offset), Some(selected)) = (tools.drag_offset, selected_agent.0) {
+            edits.send(EditAgent::Move(selected, cursor_position + offset));
+        }
+    }
+}
+
+/// Spawn agents under the brush until it contains the wanted density of agents.
+fn paint_agents(
+    buttons: Res<Input<MouseButton>>,
+    cursor: Res<WorldCursor>,
+    tools: Res<Tools>,
+    mut edits: EventWriter<EditAgent>,
+) {
+    if tools.tool != Tool::Brush || !buttons.pressed(MouseButton::Left) {
+        return;
+    }
+    let center = match cursor.position {
+        Some(position) => position,
+        None => return,
+    };
+
+    // the agents are drawn by the simulation, with its random number generator
+    edits.send(EditAgent::Paint {
+        center,
+        radius: tools.brush_radius,
+        density: tools.brush_density,
+        behaviour: tools.brush_kind,
+    });
+}
+
+/// Select the agents inside the rectangle drawn with the mouse.
+fn select_rectangle(
+    buttons: Res<Input<MouseButton>>,
+    cursor: Res<WorldCursor>,
+    mut tools: ResMut<Tools>,
+    agents: Query<(Entity, &Transform), With<Agent>>,
+) {
+    if tools.tool != Tool::Rectangle {
+        return;
+    }
+
+    if buttons.just_pressed(MouseButton::Left) {
+        tools.rectangle_start = cursor.position;
+    } else if buttons.just_released(MouseButton::Left) {
+        if let (Some(start), Some(end)) = (tools.rectangle_start.take(), cursor.position) {
+            let (min, max) = (start.min(end), start.max(end));
+            tools.selection = agents
+                .iter()
+                .filter(|(_, transform)| {
+                    let position = transform.translation.truncate();
+                    position.cmpge(min).all() && position.cmple(max).all()
+                })
+                .map(|(entity, _)| entity)
+                .collect();
+        }
+    }
+}
+
+/// Set the friend of an agent by dragging from it to another agent, or its foe with shift.
+fn link_agents(
+    buttons: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    cursor: Res<WorldCursor>,
+    simulation_settings: Res<SimulationSettings>,
+    mut tools: ResMut<Tools>,
+    mut edits: EventWriter<EditAgent>,
+    agents: Query<(Entity, &Transform, &AgentBehaviour), With<Agent>>,
+) {
+    if tools.tool != Tool::Link {
+        return;
+    }
+
+    if buttons.just_pressed(MouseButton::Left) {
+        tools.link_start = cursor
+            .agent_under(&simulation_settings, agents.iter())
+            .map(|(entity, _)| entity);
+    } else if buttons.just_released(MouseButton::Left) {
+        let end = cursor.agent_under(&simulation_settings, agents.iter());
+        if let (Some(start), Some((end, _))) = (tools.link_start.take(), end) {
+            if start != end {
+                edits.send(if is_shift_pressed(&keys) {
+                    EditAgent::SetFoe(start, end)
+                } else {
+                    EditAgent::SetFriend(start, end)
+                });
+            }
+        }
+    }
+}
+
+/// `true` if one of the shift keys is pressed.
+fn is_shift_pressed(keys: &Input<KeyCode>) -> bool {
+    keys.pressed(KeyCode::LShift) || keys.pressed(KeyCode::RShift)
+}
+
+/// Display the brush, the rectangle being drawn and the agents inside the last rectangle.
+fn display_tools(
+    tools: Res<Tools>,
+    cursor: Res<WorldCursor>,
+    keys: Res<Input<KeyCode>>,
+    simulation_settings: Res<SimulationSettings>,
+    mut lines: ResMut<DebugLines>,
+    agents: Query<(&Transform, &AgentBehaviour), With<Agent>>,
+) {
+    const MARGIN: f32 = 4.0;
+    let color = Color::rgb(0.4, 0.9, 0.9);
+
+    match (tools.tool, cursor.position) {
+        (Tool::Brush, Some(position)) => {
+            lines.circle_colored(position.extend(0.0), tools.brush_radius, 0.0, color);
+        }
+        (Tool::Rectangle, Some(end)) => {
+            if let Some(start) = tools.rectangle_start {
+                let corners = [
+                    start.extend(0.0),
+                    Vec3::new(end.x, start.y, 0.0),
+                    end.extend(0.0),
+                    Vec3::new(start.x, end.y, 0.0),
+                ];
+                for i in 0..4 {
+                    lines.line_colored(corners[i], corners[(i + 1) % 4], 0.0, color);
+                }
+            }
+        }
+        (Tool::Link, Some(end)) => {
+            if let Some(Ok((transform, _))) = tools.link_start.map(|e| agents.get(e)) {
+                let color = if is_shift_pressed(&keys) {
+                    Color::RED
+                } else {
+                    Color::GREEN
+                };
+                lines.arrow_colored(transform.translation, end.extend(0.0), 0.0, color);
+            }
+        }
+        _ => {}
+    }
+
+    for entity in &tools.selection {
+        if let Ok((transform, behaviour)) = agents.get(*entity) {
+            let radius = simulation_settings.kind(behaviour).radius + MARGIN;
+            lines.circle_colored(transform.translation, radius, 0.0, color);
+        }
+    }
+}
+
+fn ui_tools(
+    egui_context: ResMut<EguiContext>,
+    mut tools: ResMut<Tools>,
+    mut selected_agent: ResMut<SelectedAgent>,
+    mut edits: EventWriter<EditAgent>,
+) {
+    egui::Window::new("Outils").show(egui_context.ctx(), |ui| {
+        let tools = &mut *tools;
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut tools.tool, Tool::Select, "Sélection");
+            ui.selectable_value(&mut tools.tool, Tool::Brush, "Pinceau");
+            ui.selectable_value(&mut tools.tool, Tool::Rectangle, "Rectangle");
+            ui.selectable_value(&mut tools.tool, Tool::Link, "Liens");
+        });
+        if tools.tool == Tool::Link {
+            ui.label("Glisser vers l'ami, ou vers l'ennemi avec Maj.");
+        }
+
+        if tools.tool == Tool::Brush {
+            egui::Grid::new("grid_brush").show(ui, |ui| {
+                ui.label("Type");
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut tools.brush_kind, AgentBehaviour::Heroe, "Héros");
+                    ui.selectable_value(&mut tools.brush_kind, AgentBehaviour::Coward, "Lâches");
+                });
+                ui.end_row();
+
+                ui.label("Rayon");
+                ui.add(egui::DragValue::new(&mut tools.brush_radius).clamp_range(5.0..=500.0));
+                ui.end_row();
+
+                ui.label("Densité");
+                ui.add(
+                    egui::DragValue::new(&mut tools.brush_density)
+                        .clamp_range(0.0..=100.0)
+                        .speed(0.1)
+                        .suffix(" / 100²"),
+                );
+                ui.end_row();
+            });
+        }
+
+        if !tools.selection.is_empty() {
+            ui.separator();
+            ui.label(format!("{} agents sélectionnés", tools.selection.len()));
+            ui.horizontal(|ui| {
+                for (kind, label) in [
+                    (AgentBehaviour::Heroe, "Héros"),
+                    (AgentBehaviour::Coward, "Lâches"),
+                ] {
+                    if ui.button(label).clicked() {
+                        for entity in &tools.selection {
+                            edits.send(EditAgent::SetKind(*entity, kind));
+                        }
+                    }
+                }
+                for (pinned, label) in [(true, "Épingler"), (false, "Libérer")] {
+                    if ui.button(label).clicked() {
+                        for entity in &tools.selection {
+                            edits.send(EditAgent::SetPinned(*entity, pinned));
+                        }
+                    }
+                }
+                if ui.button("Supprimer").clicked() {
+                    for entity in tools.selection.drain(..) {
+                        edits.send(EditAgent::Delete(entity));
+                        if selected_agent.0 == Some(entity) {
+                            selected_agent.0 = None;
+                        }
+                    }
+                }
+                if ui.button("Désélectionner").clicked() {
+                    tools.selection.clear();
+                }
+            });
+        }
+    });
+}
@@ -1,8 +1,14 @@
+use crate::spatial::*;
 use crate::velocity::*;
-use bevy::{math::swizzles::*, prelude::*};
-use bevy_prototype_debug_lines::DebugLines;
+use bevy::{
+    math::swizzles::*,
+    prelude::*,
+    render::texture::{Extent3d, FilterMode, TextureDimension, TextureFormat},
+};
+use bevy_prototype_debug_lines::{DebugLines, MAX_LINES};
 use rand::prelude::*;
 use rand_pcg::Pcg32;
+use std::collections::{HashMap, HashSet, VecDeque};
 
 use crate::utils::*;
 
@@ -11,17 +17,26 @@ pub struct HeroesCowardSimulationPlugin;
 impl Plugin for HeroesCowardSimulationPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app
-            // state
+            // state, with a driver in each stage using it: the transitions requested in
+            // `CoreStage::First` (like a step) are applied before the update of the agents
             .add_system_set_to_stage(
                 CoreStage::PostUpdate,
                 State::<SimulationState>::get_driver(),
             )
-            .add_state(SimulationState::NotInit)
+            .add_state_to_stage(CoreStage::Update, SimulationState::NotInit)
             // resource
             .init_resource::<SimulationSpeed>()
+            .init_resource::<SimulationTemperature>()
             .init_resource::<SimulationSettings>()
             .init_resource::<SimulationDebug>()
             .init_resource::<SimStats>()
+            .init_resource::<SpatialGrid>()
+            .init_resource::<SelectedAgent>()
+            .init_resource::<SimulationStep>()
+            .init_resource::<SimulationHistory>()
+            .init_resource::<Heatmap>()
+            .init_resource::<ColorLegend>()
+            .add_event::<EditAgent>()
             // systems
             .add_startup_system(setup.system())
             .add_system_set(
@@ -29,19 +44,64 @@ impl Plugin for HeroesCowardSimulationPlugin {
                     .with_system(initialize_simulation.system()),
             )
             .add_system_set(
-                SystemSet::on_update(SimulationState::Run).with_system(update_agents.system()),
+                SystemSet::on_update(SimulationState::Run)
+                    .with_system(update_spatial_grid.system().label("spatial_grid"))
+                    .with_system(update_headings.system().label("update_headings"))
+                    .with_system(record_positions.system().label("record_positions"))
+                    .with_system(
+                        update_agents
+                            .system()
+                            .label("update_agents")
+                            .after("spatial_grid")
+                            .after("update_headings")
+                            .after("record_positions"),
+                    ),
             )
             .add_system_set_to_stage(
                 CoreStage::PostUpdate,
                 SystemSet::on_update(SimulationState::Run)
-                    .with_system(update_velocities.system().label("update_velocity"))
+                    .with_system(move_agents.system().label("update_velocity"))
+                    .with_system(
+                        resolve_collisions
+                            .system()
+                            .label("resolve_collisions")
+                            .after("update_velocity"),
+                    )
                     .with_system(
                         keep_in_arena
                             .system()
                             .label("keep_in_arena")
-                            .after("update_velocity"),
+                            .after("resolve_collisions"),
+                    )
+                    .with_system(
+                        compute_stats
+                            .system()
+                            .label("compute_stats")
+                            .after("keep_in_arena"),
+                    )
+                    .with_system(
+                        record_history
+                            .system()
+                            .label("record_history")
+                            .after("compute_stats"),
+                    )
+                    .with_system(record_trails.system().after("keep_in_arena"))
+                    .with_system(
+                        record_heatmap
+                            .system()
+                            .label("record_heatmap")
+                            .after("keep_in_arena"),
                     )
-                    .with_system(compute_stats.system().after("keep_in_arena")),
+                    .with_system(end_step.system().after("record_history")),
+            )
+            .add_system_to_stage(CoreStage::First, begin_step.system())
+            .add_system(restore_snapshot.system())
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                edit_agents
+                    .system()
+                    .after("update_velocity")
+                    .before("resolve_collisions"),
             )
             .add_system_to_stage(
                 CoreStage::PostUpdate,
@@ -50,6 +110,46 @@ impl Plugin for HeroesCowardSimulationPlugin {
             .add_system_to_stage(
                 CoreStage::PostUpdate,
                 display_center_of_mass.system().after("keep_in_arena"),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                display_vision_cone.system().after("keep_in_arena"),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                display_target_points.system().after("keep_in_arena"),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                display_velocities.system().after("keep_in_arena"),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                display_vision_ranges.system().after("keep_in_arena"),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                display_arena_bounds.system().after("keep_in_arena"),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                display_hulls.system().after("compute_stats"),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                display_selection.system().after("keep_in_arena"),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                display_trails.system().after("keep_in_arena"),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                display_heatmap.system().after("record_heatmap"),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                color_agents.system().after("keep_in_arena"),
             );
     }
 }
@@ -61,6 +161,75 @@ impl Plugin for HeroesCowardSimulationPlugin {
 pub struct SimStats {
     pub center_of_mass: Vec2,
     pub deviation: f32,
+    /// Proportion of agents overlapping at least one other agent.
+    pub overlap: f32,
+    /// Mean speed factor of the heroes and of the cowards.
+    pub mean_speed: (f32, f32),
+    /// Mean vision range of the heroes and of the cowards.
+    pub mean_vision: (f32, f32),
+    /// Mean distance between the position of the agents after their move
+    /// and the one they would have reached without the motion noise.
+    pub noise_shift: f32,
+    /// Deviation averaged over the last seconds.
+    pub mean_deviation: f32,
+    /// Kinetic energy of all the agents.
+    pub kinetic_energy: f32,
+    /// Proportion of the moving agents whose desired direction reversed during the last move,
+    /// a sign that they went past the point they wanted to reach.
+    pub overshoot: f32,
+    /// Convex hull of the heroes.
+    pub heroe_hull: Hull,
+    /// Convex hull of the cowards.
+    pub coward_hull: Hull,
+    /// Convex hull of all the agents.
+    pub hull: Hull,
+}
+
+/// Convex hull of the centers of a group of agents.
+#[derive(Default)]
+pub struct Hull {
+    /// Vertices of the hull, counterclockwise.
+    pub points: Vec<Vec2>,
+    pub area: f32,
+    pub perimeter: f32,
+}
+
+impl Hull {
+    fn new(points: Vec<Vec2>) -> Self {
+        let points = convex_hull(points);
+        Self {
+            area: polygon_area(&points),
+            perimeter: polygon_perimeter(&points),
+            points,
+        }
+    }
+}
+
+/// Event sent to modify an agent during the simulation.
+pub enum EditAgent {
+    /// Move the agent to the given position.
+    Move(Entity, Vec3),
+    /// Pin the agent in place, or release it.
+    SetPinned(Entity, bool),
+    /// Change the kind of the agent, its speed and vision are kept.
+    SetKind(Entity, AgentBehaviour),
+    /// Set the friend of the first agent to the second one.
+    /// If it was its foe, the previous friend becomes the foe.
+    SetFriend(Entity, Entity),
+    /// Set the foe of the first agent to the second one.
+    /// If it was its friend, the previous foe becomes the friend.
+    SetFoe(Entity, Entity),
+    /// Add agents of the given kind at random positions in the disk of `radius` around `center`,
+    /// until there are `density` agents per 10 000 square units in the disk.
+    /// The new agents get a random friend and foe.
+    Paint {
+        center: Vec2,
+        radius: f32,
+        density: f32,
+        behaviour: AgentBehaviour,
+    },
+    /// Remove the agent, the agents related to it choose another friend or foe.
+    Delete(Entity),
 }
 
 /// State of the simulation
@@ -78,6 +247,87 @@ pub enum SimulationState {
 pub enum BlindBehavour {
     NoMove,
     RandomMove,
+    /// Move based on the last known positions of the friend and the foe.
+    Memory,
+    /// Like [`BlindBehavour::Memory`], but move randomly once everything is forgotten.
+    MemoryThenRandom,
+    /// Reynolds' wander: steer toward a point moving randomly on a circle in front of the agent.
+    Wander,
+    /// Move toward the center of the arena.
+    ReturnToCenter,
+    /// Move toward the nearest agent in sight.
+    FollowNearest,
+    /// Explore the surroundings along an expanding spiral.
+    SpiralSearch,
+}
+
+/// How agents avoid each other.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CollisionMode {
+    /// Agents freely overlap.
+    None,
+    /// Agents are pushed away from their neighbours by a force.
+    Separation,
+    /// Overlapping agents are moved apart after each update.
+    Resolve,
+}
+
+/// How an agent that see both its friend and its foe choose where to go.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MovementModel {
+    /// Sum of the moves relative to the friend and to the foe.
+    VectorSum,
+    /// Move toward a point defined by the positions of the friend and the foe:
+    /// between them for the heroes, behind the friend for the cowards.
+    TargetPoint,
+}
+
+/// How the move toward (or away from) a target depends on its distance.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DistanceResponse {
+    /// The displacement to the target, the farther target dominates.
+    Raw,
+    /// The direction to the target, regardless of its distance.
+    Unit,
+    /// The direction to the target, weighted by the inverse of its distance.
+    InverseDistance,
+    /// The direction to the target, with a gaussian falloff with its distance.
+    Gaussian,
+}
+
+impl DistanceResponse {
+    /// Transform the displacement `offset` to a target.
+    /// `scale` is the distance at which the inverse distance response is 1
+    /// and the standard deviation of the gaussian response.
+    fn apply(&self, offset: Vec3, scale: f32) -> Vec3 {
+        let distance = offset.length();
+        match self {
+            DistanceResponse::Raw => offset,
+            DistanceResponse::Unit => offset.normalize_or_zero(),
+            DistanceResponse::InverseDistance => {
+                offset.normalize_or_zero() * scale / distance.max(1.0)
+            }
+            DistanceResponse::Gaussian => {
+                offset.normalize_or_zero() * (-distance.powi(2) / (2.0 * scale.powi(2))).exp()
+            }
+        }
+    }
+}
+
+/// In which order the agents take their decisions during a tick.
+///
+/// With the sequential schemes, the agents see the whole move of the earlier agents,
+/// with the motion noise and the limits of the arena. Only the collisions resolved with
+/// [`CollisionMode::Resolve`] are applied to all the agents after their moves.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UpdateScheme {
+    /// All the agents decide from the state at the beginning of the tick.
+    Synchronous,
+    /// The agents decide one after the other in a random order,
+    /// and see the moves of the agents that decided before them.
+    RandomSequential,
+    /// Same as [`UpdateScheme::RandomSequential`], but always in the order of creation of the agents.
+    FixedSequential,
 }
 
 /// Settings for the simulation.
@@ -86,10 +336,50 @@ pub struct SimulationSettings {
     pub seed: u64,
     pub agent_count: usize,
     pub heroe_proportion: f64,
-    pub blind_behaviour: BlindBehavour,
     pub arena_size: f32,
-    pub use_vision_limit: bool,
-    pub vision_limit: f32,
+    /// If `true`, agents can't see through other agents.
+    pub use_occlusion: bool,
+    pub collision: CollisionMode,
+    pub update_scheme: UpdateScheme,
+    pub movement_model: MovementModel,
+    /// Position of the heroes' target point between the friend (0) and the foe (1).
+    pub heroe_fraction: f32,
+    /// Distance behind the friend of the cowards' target point.
+    pub hiding_distance: f32,
+    pub distance_response: DistanceResponse,
+    /// Scale of the [`DistanceResponse`], in distance unit.
+    pub response_scale: f32,
+    /// If `true`, agents react to the future position of their foe, predicted from its velocity.
+    pub use_anticipation: bool,
+    /// How far in the future the position of the foe is predicted, in seconds.
+    pub anticipation_horizon: f32,
+    /// Standard deviation of the error on the perceived positions of the friend and the foe.
+    pub perception_noise: f32,
+    /// Number of ticks between the moment a position is seen and the moment the agent react to it.
+    pub perception_delay: usize,
+    pub heroe: KindSettings,
+    pub coward: KindSettings,
+    /// If `true`, agents only see in front of them.
+    pub use_field_of_view: bool,
+    /// Angle of the field of view, in degrees.
+    pub field_of_view: f32,
+    /// Time after which an agent forget the last known position of its friend or foe, in seconds.
+    pub memory_duration: f32,
+    /// Distance of the wander circle in front of the agent.
+    pub wander_distance: f32,
+    /// Radius of the wander circle.
+    pub wander_radius: f32,
+    /// Random displacement per second of the point on the wander circle.
+    pub wander_jitter: f32,
+    /// Distance between two loops of the search spiral.
+    pub spiral_spacing: f32,
+    /// If `true`, agents accelerate toward their desired velocity instead of turning instantly.
+    pub use_steering: bool,
+    pub steering: SteeringSettings,
+    /// Numerical method used to move the agents.
+    pub integrator: Integrator,
+    /// Number of integration steps per frame.
+    pub sub_steps: usize,
 }
 
 impl Default for SimulationSettings {
@@ -98,10 +388,142 @@ impl Default for SimulationSettings {
             seed: 0,
             agent_count: 30,
             heroe_proportion: 0.5,
-            blind_behaviour: BlindBehavour::NoMove,
             arena_size: 300.0,
+            use_occlusion: false,
+            collision: CollisionMode::None,
+            update_scheme: UpdateScheme::Synchronous,
+            movement_model: MovementModel::VectorSum,
+            heroe_fraction: 0.5,
+            hiding_distance: 20.0,
+            distance_response: DistanceResponse::Raw,
+            response_scale: 50.0,
+            use_anticipation: false,
+            anticipation_horizon: 1.0,
+            perception_noise: 0.0,
+            perception_delay: 0,
+            heroe: Default::default(),
+            coward: Default::default(),
+            use_field_of_view: false,
+            field_of_view: 180.0,
+            memory_duration: 5.0,
+            wander_distance: 2.0,
+            wander_radius: 1.0,
+            wander_jitter: 4.0,
+            spiral_spacing: 40.0,
+            use_steering: false,
+            steering: Default::default(),
+            integrator: Integrator::SemiImplicitEuler,
+            sub_steps: 1,
+        }
+    }
+}
+
+impl SimulationSettings {
+    /// The settings for the given kind of agent.
+    pub fn kind(&self, behaviour: &AgentBehaviour) -> &KindSettings {
+        match behaviour {
+            AgentBehaviour::Heroe => &self.heroe,
+            AgentBehaviour::Coward => &self.coward,
+        }
+    }
+}
+
+/// Settings specific to a kind of agent.
+#[derive(Debug, Clone)]
+pub struct KindSettings {
+    /// Physical radius of the agents.
+    pub radius: f32,
+    /// Strength of the force that push the agents away from their neighbours.
+    pub separation: f32,
+    /// Distribution of the speed of the agents, relative to the [`SimulationSpeed`].
+    pub speed: ParamDistribution,
+    /// Factor applied to the speed of the agents.
+    pub speed_multiplier: f32,
+    pub use_vision_limit: bool,
+    /// Distribution of the vision range of the agents.
+    pub vision: ParamDistribution,
+    pub blind_behaviour: BlindBehavour,
+    /// Weight of the move relative to the friend.
+    pub friend_weight: f32,
+    /// Weight of the move relative to the foe.
+    pub foe_weight: f32,
+}
+
+impl Default for KindSettings {
+    fn default() -> Self {
+        Self {
+            radius: 8.0,
+            separation: 1.0,
+            speed: ParamDistribution::Constant(1.0),
+            speed_multiplier: 1.0,
             use_vision_limit: false,
-            vision_limit: 30.0,
+            vision: ParamDistribution::Constant(30.0),
+            blind_behaviour: BlindBehavour::NoMove,
+            friend_weight: 1.0,
+            foe_weight: 1.0,
+        }
+    }
+}
+
+/// Distribution from which a parameter is drawn for each agent.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParamDistribution {
+    Constant(f32),
+    /// Uniform distribution between a min and a max.
+    Uniform(f32, f32),
+    Normal {
+        mean: f32,
+        std_dev: f32,
+    },
+}
+
+impl ParamDistribution {
+    pub fn mean(&self) -> f32 {
+        match self {
+            ParamDistribution::Constant(value) => *value,
+            ParamDistribution::Uniform(min, max) => (min + max) / 2.0,
+            ParamDistribution::Normal { mean, .. } => *mean,
+        }
+    }
+
+    /// Draw a value, negative values are clamped to zero.
+    pub fn sample(&self, rng: &mut impl Rng) -> f32 {
+        let value = match self {
+            ParamDistribution::Constant(value) => *value,
+            ParamDistribution::Uniform(min, max) if min < max => rng.gen_range(*min..*max),
+            ParamDistribution::Uniform(min, _) => *min,
+            ParamDistribution::Normal { mean, std_dev } => mean + std_dev * standard_normal(rng),
+        };
+        value.max(0.0)
+    }
+}
+
+/// Settings for the inertial steering model.
+/// Speeds and forces are relative to the [`SimulationSpeed`].
+///
+/// The steering force is proportional to the gap with the desired speed,
+/// so the drag lowers the terminal speed to `speed / (1 + mass * drag)`.
+#[derive(Debug, Clone)]
+pub struct SteeringSettings {
+    pub mass: f32,
+    /// Maximum steering force, per second.
+    pub max_force: f32,
+    pub max_speed: f32,
+    /// Fraction of the velocity lost per second.
+    pub drag: f32,
+    /// Distance to the target under which the agent slow down.
+    pub arrive_radius: f32,
+}
+
+impl Default for SteeringSettings {
+    fn default() -> Self {
+        Self {
+            mass: 1.0,
+            max_force: 4.0,
+            max_speed: 1.5,
+            // terminal speed at about 95% of the speed
+            drag: 0.05,
+            arrive_radius: 50.0,
         }
     }
 }
@@ -109,13 +531,207 @@ impl Default for SimulationSettings {
 /// Define the speed of the agents
 pub struct SimulationSpeed(pub f32);
 
-/// Settings for debug display
+/// Define the amplitude of the random perturbation of the agents' motion,
+/// as a diffusion coefficient relative to their speed, see [`motion_noise_amplitude`].
 #[derive(Default)]
+pub struct SimulationTemperature(pub f32);
+
+/// Settings for debug display
 pub struct SimulationDebug {
     pub display_friend_links: bool,
     pub display_foe_links: bool,
     pub center_of_mass: bool,
     pub deviation: bool,
+    pub vision_cone: bool,
+    pub target_points: OverlayScope,
+    pub velocities: OverlayScope,
+    /// Circle of the vision range of the agents with a limited vision.
+    pub vision_ranges: OverlayScope,
+    pub arena_bounds: bool,
+    /// Convex hulls of the heroes, of the cowards and of all the agents.
+    pub hulls: bool,
+    /// Display the trail of every agent.
+    pub trails: bool,
+    /// Display the trail of the [`SelectedAgent`] only.
+    pub selected_trail: bool,
+    /// Number of past positions in a trail.
+    pub trail_length: usize,
+    pub heatmap: bool,
+    pub heatmap_mode: HeatmapMode,
+    /// Kind of agent counted in the heatmap, all of them if `None`.
+    pub heatmap_kind: Option<AgentBehaviour>,
+    pub color_mode: ColorMode,
+    /// Maximum distance between two agents of the same cluster, for [`ColorMode::Cluster`].
+    pub cluster_distance: f32,
+}
+
+impl Default for SimulationDebug {
+    fn default() -> Self {
+        Self {
+            display_friend_links: false,
+            display_foe_links: false,
+            center_of_mass: false,
+            deviation: false,
+            vision_cone: false,
+            target_points: OverlayScope::Hidden,
+            velocities: OverlayScope::Hidden,
+            vision_ranges: OverlayScope::Hidden,
+            arena_bounds: false,
+            hulls: false,
+            trails: false,
+            selected_trail: false,
+            trail_length: 50,
+            heatmap: false,
+            heatmap_mode: HeatmapMode::Integrated,
+            heatmap_kind: None,
+            color_mode: ColorMode::Kind,
+            cluster_distance: 30.0,
+        }
+    }
+}
+
+/// Which agents a debug overlay is displayed for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OverlayScope {
+    Hidden,
+    /// Only the [`SelectedAgent`].
+    Selected,
+    All,
+}
+
+impl OverlayScope {
+    /// Whether the overlay is displayed for the given agent.
+    fn shows(&self, entity: Entity, selected_agent: &SelectedAgent) -> bool {
+        match self {
+            OverlayScope::Hidden => false,
+            OverlayScope::Selected => selected_agent.0 == Some(entity),
+            OverlayScope::All => true,
+        }
+    }
+}
+
+/// What the color of the agents shows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorMode {
+    Kind,
+    /// Norm of the velocity, on the gradient.
+    Speed,
+    /// Distance to the foe, on the gradient.
+    FoeDistance,
+    /// Group of agents closer than [`SimulationDebug::cluster_distance`] to each other,
+    /// the isolated agents are neutral.
+    Cluster,
+    /// Whether the agent sees its friend and its foe,
+    /// the color is the category `friend + 2 * foe` of [`CATEGORY_COLORS`].
+    Visibility,
+    /// Number of agents whose foe is the agent, on the gradient.
+    FoeInDegree,
+}
+
+/// Number of colors of the gradient used for the continuous [`ColorMode`]s.
+const GRADIENT_STEPS: usize = 16;
+
+/// Colors used for the categories of the [`ColorMode`]s, they are cycled if needed.
+pub const CATEGORY_COLORS: [Color; 8] = [
+    Color::rgb(0.9, 0.3, 0.3),
+    Color::rgb(0.3, 0.8, 0.3),
+    Color::rgb(0.3, 0.5, 1.0),
+    Color::rgb(1.0, 0.8, 0.2),
+    Color::rgb(0.8, 0.4, 1.0),
+    Color::rgb(0.2, 0.9, 0.9),
+    Color::rgb(1.0, 0.5, 0.1),
+    Color::rgb(1.0, 0.5, 0.8),
+];
+
+/// Color of the agents without a value for the current [`ColorMode`].
+pub const NEUTRAL_COLOR: Color = Color::rgb(0.5, 0.5, 0.5);
+
+/// Color of the gradient at `t` between 0 (low values) and 1 (high values).
+pub fn gradient_color(t: f32) -> Color {
+    Color::hsl(240.0 * (1.0 - t.clamp(0.0, 1.0)), 0.9, 0.5)
+}
+
+/// Range of the values shown by the [`ColorMode`], for the legend.
+#[derive(Default)]
+pub struct ColorLegend {
+    /// The value at the end of the gradient.
+    pub max: f32,
+    /// Number of clusters of at least two agents.
+    pub clusters: usize,
+}
+
+/// What the [`Heatmap`] displays.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HeatmapMode {
+    /// The agents in each cell during the last tick.
+    Instantaneous,
+    /// The mean number of agents in each cell since the start of the simulation.
+    Integrated,
+}
+
+/// Number of cells of the [`Heatmap`] along each side of the arena.
+pub const HEATMAP_RESOLUTION: usize = 64;
+
+/// Occupancy of the arena, on a grid of [`HEATMAP_RESOLUTION`]² cells.
+/// The cells are stored by row, from the bottom left corner of the arena.
+#[derive(Default)]
+pub struct Heatmap {
+    /// Number of heroes and cowards in each cell during the last tick.
+    current: Vec<[u32; 2]>,
+    /// Sum of `current` over the recorded ticks.
+    total: Vec<[u64; 2]>,
+    ticks: u64,
+}
+
+impl Heatmap {
+    /// Occupancy of each cell by the agents of the given kind, or all of them.
+    pub fn values(&self, mode: HeatmapMode, kind: Option<AgentBehaviour>) -> Vec<f32> {
+        let kinds = match kind {
+            Some(AgentBehaviour::Heroe) => 0..1,
+            Some(AgentBehaviour::Coward) => 1..2,
+            None => 0..2,
+        };
+        match mode {
+            HeatmapMode::Instantaneous => self
+                .current
+                .iter()
+                .map(|counts| counts[kinds.clone()].iter().sum::<u32>() as f32)
+                .collect(),
+            HeatmapMode::Integrated => self
+                .total
+                .iter()
+                .map(|counts| {
+                    counts[kinds.clone()].iter().sum::<u64>() as f32 / self.ticks.max(1) as f32
+                })
+                .collect(),
+        }
+    }
+
+    /// RGBA pixels of the heatmap, by row from the top left corner of the arena.
+    /// The most occupied cell is opaque.
+    pub fn image(&self, mode: HeatmapMode, kind: Option<AgentBehaviour>) -> Vec<u8> {
+        let color = match kind {
+            Some(behaviour) => kind_color(&behaviour),
+            None => Color::rgb(1.0, 0.2, 0.3),
+        };
+        let values = self.values(mode, kind);
+        let max = values.iter().copied().fold(0.0, f32::max);
+
+        let mut image = vec![0; HEATMAP_RESOLUTION * HEATMAP_RESOLUTION * 4];
+        if max > 0.0 {
+            for (row, pixels) in image.chunks_mut(HEATMAP_RESOLUTION * 4).enumerate() {
+                let cells = (HEATMAP_RESOLUTION - 1 - row) * HEATMAP_RESOLUTION;
+                for (pixel, value) in pixels.chunks_mut(4).zip(&values[cells..]) {
+                    let mut color = color;
+                    color.set_a(value / max);
+                    for (channel, component) in pixel.iter_mut().zip(color.as_rgba_f32()) {
+                        *channel = (component * 255.0) as u8;
+                    }
+                }
+            }
+        }
+        image
+    }
 }
 
 impl Default for SimulationSpeed {
@@ -125,11 +741,116 @@ impl Default for SimulationSpeed {
 }
 
 /// Hold a list of agent entity.
-struct Agents(pub Vec<Entity>);
+pub struct Agents(pub Vec<Entity>);
+
+/// The agent currently observed by the user.
+#[derive(Default)]
+pub struct SelectedAgent(pub Option<Entity>);
+
+/// Marker component for the sprite displaying the [`Heatmap`].
+struct HeatmapSprite;
 
 /// Random number generator used by the simulation
 struct SimRng(Pcg32);
 
+/// If `true`, the simulation runs a single tick then pauses.
+#[derive(Default)]
+pub struct SimulationStep(pub bool);
+
+/// Maximum number of ticks kept in the [`SimulationHistory`].
+const HISTORY_LENGTH: usize = 1000;
+
+/// Approximate size in bytes of the states kept in the [`SimulationHistory`], over all its ticks,
+/// so large simulations keep fewer ticks.
+const HISTORY_BYTES: usize = 64 << 20;
+
+/// The last states of the simulation, used to rewind it.
+#[derive(Default)]
+pub struct SimulationHistory {
+    snapshots: VecDeque<Snapshot>,
+    /// Index of the snapshot the simulation has been restored to, if any.
+    current: Option<usize>,
+    /// Index of the snapshot to restore.
+    requested: Option<usize>,
+    /// Number of ticks since the beginning of the simulation.
+    tick: u64,
+}
+
+impl SimulationHistory {
+    /// Number of stored states.
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    /// Index of the current state of the simulation.
+    pub fn current(&self) -> Option<usize> {
+        self.current.or_else(|| self.snapshots.len().checked_sub(1))
+    }
+
+    /// Tick of the state at `index`.
+    pub fn tick(&self, index: usize) -> Option<u64> {
+        self.snapshots.get(index).map(|snapshot| snapshot.tick)
+    }
+
+    /// Restore the simulation to the state at `index`.
+    pub fn restore(&mut self, index: usize) {
+        self.requested = Some(index);
+    }
+}
+
+/// State of the simulation at the end of a tick.
+struct Snapshot {
+    tick: u64,
+    rng: Pcg32,
+    /// Running average of [`SimStats::mean_deviation`].
+    mean_deviation: f32,
+    /// The agents, in the order of [`Agents`].
+    agents: Vec<AgentSnapshot>,
+    /// Approximate size of the snapshot in bytes.
+    bytes: usize,
+}
+
+/// State of an agent at the end of a tick.
+struct AgentSnapshot {
+    behaviour: AgentBehaviour,
+    position: Vec3,
+    velocity: Vec3,
+    heading: Vec3,
+    /// The [`PositionHistory`], at most `perception_delay + 1` entries.
+    history: Vec<(Vec3, Vec3)>,
+    /// Index of the friend and the foe in [`Agents`].
+    relation: Option<(usize, usize)>,
+    memory: Memory,
+    blind_state: BlindState,
+    target: Option<Vec3>,
+    speed: f32,
+    vision: f32,
+    pinned: bool,
+}
+
+/// State of an agent as seen by the other agents during [`update_agents`].
+struct Observed {
+    position: Vec3,
+    velocity: Vec3,
+    /// Position and velocity `perception_delay` ticks ago.
+    delayed: (Vec3, Vec3),
+}
+
+impl Observed {
+    /// The position and the velocity of the agent perceived with a delay of `delay` ticks.
+    fn perceived(&self, delay: usize) -> (Vec3, Vec3) {
+        if delay == 0 {
+            (self.position, self.velocity)
+        } else {
+            self.delayed
+        }
+    }
+}
+
 // ===== components =====
 
 /// Tag for the agents.
@@ -139,35 +860,292 @@ pub struct Agent;
 struct Arena;
 
 /// Component that define the agent behaviour.
-enum AgentBehaviour {
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AgentBehaviour {
     Heroe,
     Coward,
 }
 
+impl AgentBehaviour {
+    /// Signed weight of the move relative to the foe based on the behaviour of the agent:
+    /// the heroes move toward their foe and the cowards move away from it.
+    fn foe_weight(&self, weight: f32) -> f32 {
+        match self {
+            AgentBehaviour::Heroe => weight,
+            AgentBehaviour::Coward => -weight,
+        }
+    }
+}
+
 /// Component that hold witch entity is the friend and foe of the agent.
-struct FriendFoe(Entity, Entity);
+#[derive(Clone, Copy)]
+pub struct FriendFoe(pub Entity, pub Entity);
+
+/// Component that hold the last positions of the agent with its velocity at that time,
+/// the most recent first. Used to delay the perception of the other agents.
+#[derive(Default)]
+struct PositionHistory(VecDeque<(Vec3, Vec3)>);
+
+impl PositionHistory {
+    /// The position and the velocity `delay` ticks ago, or the oldest ones known.
+    fn get(&self, delay: usize) -> Option<(Vec3, Vec3)> {
+        self.0.get(delay).or_else(|| self.0.back()).copied()
+    }
+}
+
+/// Component that hold the last positions of an agent, most recent first.
+/// Only used to display its trajectory.
+#[derive(Default)]
+struct Trail(VecDeque<Vec3>);
+
+/// Component that hold the point the agent is moving to, if any.
+#[derive(Default)]
+pub struct Target(pub Option<Vec3>);
+
+/// Component that hold what the agent decided to move relative to during the current tick.
+/// Its desired velocity is derived from it at any position, so its move can be integrated
+/// in several steps.
+#[derive(Default)]
+struct Intent {
+    /// Points the agent moves relative to, with the weight of the move and whether
+    /// the [`DistanceResponse`] applies. The agent moves away from the points with a negative weight.
+    points: Vec<(Vec3, f32, bool)>,
+    /// Direction followed by the agents without target.
+    direction: Option<Vec3>,
+    /// Speed of the agent, in distance unit per second.
+    speed: f32,
+    /// Push away from the agents it overlaps.
+    separation: Vec3,
+    /// Random perturbation added to its velocity, see [`motion_noise_amplitude`].
+    noise: Vec3,
+}
+
+impl Intent {
+    /// Displacement from `position` to the target of the agent,
+    /// `None` if it only follows a direction.
+    fn offset(&self, position: Vec3) -> Option<Vec3> {
+        match self.direction {
+            Some(_) => None,
+            None => Some(
+                self.points
+                    .iter()
+                    .fold(Vec3::ZERO, |offset, (point, weight, _)| {
+                        offset + (*point - position) * *weight
+                    }),
+            ),
+        }
+    }
+
+    /// Direction the agent wants to move to from `position`, not normalized.
+    fn direction(&self, position: Vec3, settings: &SimulationSettings) -> Vec3 {
+        self.direction.unwrap_or_else(|| {
+            self.points
+                .iter()
+                .fold(Vec3::ZERO, |direction, (point, weight, responded)| {
+                    let offset = *point - position;
+                    let offset = if *responded {
+                        settings
+                            .distance_response
+                            .apply(offset, settings.response_scale)
+                    } else {
+                        offset
+                    };
+                    direction + offset * *weight
+                })
+        })
+    }
+
+    /// Velocity the agent wants to have at `position`.
+    fn desired_velocity(&self, position: Vec3, settings: &SimulationSettings) -> Vec3 {
+        let velocity = self.direction(position, settings).normalize_or_zero() * self.speed;
+        if settings.use_steering {
+            // slow down when arriving at the target
+            let arrival = self.offset(position).map_or(1.0, |offset| {
+                (offset.length() / settings.steering.arrive_radius).min(1.0)
+            });
+            velocity * arrival + self.separation
+        } else {
+            velocity + self.separation
+        }
+    }
+
+    /// Acceleration of the agent with the steering model.
+    fn acceleration(&self, position: Vec3, velocity: Vec3, settings: &SimulationSettings) -> Vec3 {
+        let steering = &settings.steering;
+        let force = (self.desired_velocity(position, settings) - velocity)
+            .clamp_length_max(steering.max_force * self.speed);
+        force / steering.mass - velocity * steering.drag
+    }
+
+    /// Position and velocity of the agent after moving for `delta` seconds
+    /// from `position` with `velocity`.
+    fn integrate(
+        &self,
+        position: Vec3,
+        velocity: Vec3,
+        delta: f32,
+        settings: &SimulationSettings,
+    ) -> (Vec3, Vec3) {
+        let steps = settings.sub_steps.max(1);
+        let step = delta / steps as f32;
+        let integrator = settings.integrator;
+
+        if settings.use_steering {
+            let max_speed = settings.steering.max_speed * self.speed;
+            (0..steps).fold((position, velocity), |(position, velocity), _| {
+                let (position, velocity) = integrator.step(position, velocity, step, |p, v| {
+                    self.acceleration(p, v, settings)
+                });
+                (position, velocity.clamp_length_max(max_speed))
+            })
+        } else {
+            // the perturbation of the velocity since the decision is kept along the move
+            let initial = self.desired_velocity(position, settings);
+            let end = (0..steps).fold(position, |position, _| {
+                integrator.step_position(position, step, |p| {
+                    velocity + (self.desired_velocity(p, settings) - initial)
+                })
+            });
+            let velocity = if delta > 0.0 {
+                (end - position) / delta
+            } else {
+                velocity
+            };
+            (end, velocity)
+        }
+    }
+}
+
+/// Component that hold the speed of the agent, relative to the [`SimulationSpeed`].
+pub struct Speed(pub f32);
+
+/// Component that hold the distance up to which the agent can see, if the vision is limited.
+pub struct VisionRange(pub f32);
+
+/// Component that hold the last known position of the friend and the foe of the agent,
+/// with the time elapsed since they were seen.
+#[derive(Default, Clone)]
+struct Memory {
+    friend: Option<(Vec3, f32)>,
+    foe: Option<(Vec3, f32)>,
+}
+
+impl Memory {
+    /// Make the memories older and forget those older than `duration`.
+    fn age(&mut self, delta: f32, duration: f32) {
+        for memory in [&mut self.friend, &mut self.foe] {
+            if let Some((_, age)) = memory {
+                *age += delta;
+                if *age > duration {
+                    *memory = None;
+                }
+            }
+        }
+    }
+}
+
+/// Tag for the agents that don't move.
+pub struct Pinned;
+
+/// Component that hold whether the agent saw its friend and its foe during the last tick.
+#[derive(Default)]
+pub struct Sight {
+    pub friend: bool,
+    pub foe: bool,
+}
+
+/// Component that hold the state of the agent's blind behaviour.
+#[derive(Default, Clone)]
+struct BlindState {
+    /// The target on the wander circle, relative to its center.
+    wander: Vec3,
+    /// Time elapsed since the agent lost sight of both its friend and its foe.
+    blind_time: f32,
+    /// Angle of the heading of the agent when it lost sight of them, where the spiral search starts.
+    spiral_phase: f32,
+}
 
 /// Resource to hold materials for the agents.
 struct AgentMaterials {
     heroe_material: Handle<ColorMaterial>,
     coward_material: Handle<ColorMaterial>,
+    /// A white disc tinted with the colors of [`gradient_color`].
+    gradient: Vec<Handle<ColorMaterial>>,
+    /// A white disc tinted with the [`CATEGORY_COLORS`].
+    categories: Vec<Handle<ColorMaterial>>,
+    /// A white disc tinted with the [`NEUTRAL_COLOR`].
+    neutral: Handle<ColorMaterial>,
+}
+
+impl AgentMaterials {
+    /// The material of the given kind of agent.
+    fn get(&self, behaviour: &AgentBehaviour) -> Handle<ColorMaterial> {
+        match behaviour {
+            AgentBehaviour::Heroe => self.heroe_material.clone(),
+            AgentBehaviour::Coward => self.coward_material.clone(),
+        }
+    }
+
+    /// The material of the gradient at `t` between 0 and 1.
+    fn gradient(&self, t: f32) -> Handle<ColorMaterial> {
+        let step = (t.clamp(0.0, 1.0) * (GRADIENT_STEPS - 1) as f32).round() as usize;
+        self.gradient[step].clone()
+    }
+
+    /// The material of the given category.
+    fn category(&self, category: usize) -> Handle<ColorMaterial> {
+        self.categories[category % self.categories.len()].clone()
+    }
+}
+
+/// Color of the given kind of agent, matching its sprite, used by the overlays.
+pub fn kind_color(behaviour: &AgentBehaviour) -> Color {
+    match behaviour {
+        AgentBehaviour::Heroe => Color::CYAN,
+        AgentBehaviour::Coward => Color::YELLOW,
+    }
+}
+
+/// White disc with the size of the agent sprites.
+fn disc_texture() -> Texture {
+    const SIZE: usize = 128;
+    let mut texture = Texture::new_fill(
+        Extent3d::new(SIZE as u32, SIZE as u32, 1),
+        TextureDimension::D2,
+        &[255, 255, 255, 0],
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    let center = Vec2::splat(SIZE as f32 / 2.0);
+    for (index, pixel) in texture.data.chunks_mut(4).enumerate() {
+        let position = Vec2::new((index % SIZE) as f32, (index / SIZE) as f32) + Vec2::splat(0.5);
+        // antialiased edge
+        let coverage = (center.x - position.distance(center)).clamp(0.0, 1.0);
+        pixel[3] = (coverage * 255.0) as u8;
+    }
+    texture
 }
 
 /// Bundle for agent.
-/// [`FriendFoe`] isn't included.
+/// [`FriendFoe`], [`Heading`], [`Speed`] and [`VisionRange`] aren't included.
 #[derive(Bundle)]
 struct AgentBundle {
     #[bundle]
     sprite: SpriteBundle,
     behaviour: AgentBehaviour,
     velocity: Velocity,
+    memory: Memory,
+    blind_state: BlindState,
+    sight: Sight,
+    target: Target,
+    intent: Intent,
+    position_history: PositionHistory,
+    trail: Trail,
     agent: Agent,
 }
 
 impl AgentBundle {
     fn new(material: Handle<ColorMaterial>, x: f32, y: f32, behaviour: AgentBehaviour) -> Self {
-        let mut transform = Transform::from_xyz(x, y, 0.0);
-        transform.scale = Vec3::splat(1.0 / 8.0);
+        let transform = agent_transform(Vec3::new(x, y, 0.0));
 
         Self {
             sprite: SpriteBundle {
@@ -177,35 +1155,87 @@ impl AgentBundle {
             },
             behaviour,
             velocity: Velocity::default(),
+            memory: Memory::default(),
+            blind_state: BlindState::default(),
+            sight: Sight::default(),
+            target: Target::default(),
+            intent: Intent::default(),
+            position_history: PositionHistory::default(),
+            trail: Trail::default(),
             agent: Agent,
         }
     }
 }
 
+/// Transform of an agent sprite at the given position.
+fn agent_transform(position: Vec3) -> Transform {
+    let mut transform = Transform::from_translation(position);
+    transform.scale = Vec3::splat(1.0 / 8.0);
+    transform
+}
+
 // ===== systems =====
 
 fn setup(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut materials: ResMut<Assets<ColorMaterial>>,
+    mut textures: ResMut<Assets<Texture>>,
 ) {
     // load agent materials
     let heroe_material = materials.add(asset_server.load("heroe.png").into());
     let coward_material = materials.add(asset_server.load("coward.png").into());
 
+    let disc = textures.add(disc_texture());
+    let mut tinted_disc = |color| {
+        materials.add(ColorMaterial {
+            color,
+            texture: Some(disc.clone()),
+        })
+    };
+    let gradient = (0..GRADIENT_STEPS)
+        .map(|step| tinted_disc(gradient_color(step as f32 / (GRADIENT_STEPS - 1) as f32)))
+        .collect();
+    let categories = CATEGORY_COLORS.iter().map(|c| tinted_disc(*c)).collect();
+    let neutral = tinted_disc(NEUTRAL_COLOR);
+
     commands.insert_resource(AgentMaterials {
         heroe_material,
         coward_material,
+        gradient,
+        categories,
+        neutral,
     });
 
-    // spawn the arena
+    // spawn the arena, under the heatmap and the agents
+    // (the 2d camera only sees down to a depth of -0.1)
     commands
         .spawn_bundle(SpriteBundle {
             material: materials.add(asset_server.load("arena.png").into()),
-            transform: Transform::from_scale(Vec3::splat(1.0)),
+            transform: Transform::from_xyz(0.0, 0.0, -0.02),
             ..Default::default()
         })
         .insert(Arena);
+
+    // spawn the heatmap
+    let mut texture = Texture::new_fill(
+        Extent3d::new(HEATMAP_RESOLUTION as u32, HEATMAP_RESOLUTION as u32, 1),
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    texture.sampler.mag_filter = FilterMode::Nearest;
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: materials.add(textures.add(texture).into()),
+            transform: Transform::from_xyz(0.0, 0.0, -0.01),
+            visible: Visible {
+                is_visible: false,
+                is_transparent: true,
+            },
+            ..Default::default()
+        })
+        .insert(HeatmapSprite);
 }
 
 /// Initialize the simulation.
@@ -214,6 +1244,7 @@ fn initialize_simulation(
     mut simulation_state: ResMut<State<SimulationState>>,
     materials: Res<AgentMaterials>,
     simulation_settings: Res<SimulationSettings>,
+    mut selected_agent: ResMut<SelectedAgent>,
     agents: Option<Res<Agents>>,
     mut arena: Query<&mut Transform, With<Arena>>,
 ) {
@@ -231,6 +1262,7 @@ fn initialize_simulation(
 
     let mut rng = Pcg32::seed_from_u64(simulation_settings.seed);
     let mut entities = Vec::with_capacity(simulation_settings.agent_count);
+    let mut behaviours = Vec::with_capacity(simulation_settings.agent_count);
 
     // create agent entities
     for _ in 0..simulation_settings.agent_count {
@@ -243,6 +1275,7 @@ fn initialize_simulation(
         // Get a random position in the arena
         let x = rng.gen_range(-simulation_settings.arena_size..simulation_settings.arena_size);
         let y = rng.gen_range(-simulation_settings.arena_size..simulation_settings.arena_size);
+        behaviours.push(behaviour);
         entities.push(
             commands
                 .spawn_bundle(AgentBundle::new(material, x, y, behaviour))
@@ -252,116 +1285,1000 @@ fn initialize_simulation(
 
     // set a random friend and foe to each agents
     for entity in &entities {
-        let mut friend: &Entity;
-        let mut foe: &Entity;
-        loop {
-            friend = entities
-                .get(rng.gen_range(0..simulation_settings.agent_count))
-                .unwrap();
-            if friend != entity {
-                break;
-            }
-        }
-        loop {
-            foe = entities
-                .get(rng.gen_range(0..simulation_settings.agent_count))
-                .unwrap();
-            if foe != entity && foe != friend {
-                break;
-            }
+        let friend = random_other(&mut rng, &entities, &[*entity]);
+        let foe = friend.and_then(|friend| random_other(&mut rng, &entities, &[*entity, friend]));
+        if let (Some(friend), Some(foe)) = (friend, foe) {
+            commands.entity(*entity).insert(FriendFoe(friend, foe));
         }
+    }
+
+    // set a random heading to each agents
+    for entity in &entities {
+        let a = rng.gen_range(0.0..std::f32::consts::TAU);
+        commands
+            .entity(*entity)
+            .insert(Heading(Vec3::new(a.cos(), a.sin(), 0.0)));
+    }
+
+    // draw the speed and the vision of each agents
+    for (entity, behaviour) in entities.iter().zip(&behaviours) {
+        let kind = simulation_settings.kind(behaviour);
+        let speed = kind.speed.sample(&mut rng);
+        let vision = kind.vision.sample(&mut rng);
         commands
-            .entity(entity.clone())
-            .insert(FriendFoe(friend.clone(), foe.clone()));
+            .entity(*entity)
+            .insert_bundle((Speed(speed), VisionRange(vision)));
     }
 
+    // the user picks the agent to observe
+    selected_agent.0 = None;
+
     // keep trace of agent entities to despawn them
     // if the simulation restart.
     commands.insert_resource(Agents(entities));
 
     commands.insert_resource(SimRng(rng));
+    commands.insert_resource(SimStats::default());
+    commands.insert_resource(SimulationHistory::default());
+    commands.insert_resource(Heatmap::default());
 
     simulation_state.set(SimulationState::Run).unwrap();
 }
 
-/// Update agents velocity component.
+/// Start the tick requested with [`SimulationStep`].
+/// It runs in `CoreStage::First`, so the whole tick runs in `Run` from `CoreStage::Update` on.
+fn begin_step(
+    mut step: ResMut<SimulationStep>,
+    mut simulation_state: ResMut<State<SimulationState>>,
+) {
+    if !step.0 {
+        return;
+    }
+    if *simulation_state.current() == SimulationState::Pause {
+        let _ = simulation_state.overwrite_set(SimulationState::Run);
+    } else {
+        // not paused, there is no tick to run
+        step.0 = false;
+    }
+}
+
+/// Pause the simulation after the tick requested with [`SimulationStep`].
+fn end_step(
+    mut step: ResMut<SimulationStep>,
+    mut simulation_state: ResMut<State<SimulationState>>,
+) {
+    if step.0 {
+        step.0 = false;
+        // a reset requested during the tick is already queued and takes precedence
+        let _ = simulation_state.set(SimulationState::Pause);
+    }
+}
+
+/// The components stored in the [`SimulationHistory`].
+type RecordedAgent<'a> = (
+    &'a Transform,
+    &'a Velocity,
+    &'a Heading,
+    &'a PositionHistory,
+    &'a AgentBehaviour,
+    Option<&'a FriendFoe>,
+    &'a Memory,
+    &'a BlindState,
+    &'a Target,
+    &'a Speed,
+    &'a VisionRange,
+    Option<&'a Pinned>,
+);
+
+/// Store the state of the simulation in the [`SimulationHistory`].
+fn record_history(
+    agents: Res<Agents>,
+    rng: Res<SimRng>,
+    stats: Res<SimStats>,
+    mut history: ResMut<SimulationHistory>,
+    query: Query<RecordedAgent<'_>>,
+) {
+    // resuming from a restored state discards the states after it
+    if let Some(current) = history.current.take() {
+        history.snapshots.truncate(current + 1);
+    }
+
+    // the agents spawned during this tick are complete at the next one
+    let recorded: Vec<_> = agents
+        .0
+        .iter()
+        .filter_map(|entity| query.get(*entity).ok())
+        .collect();
+    let indices: HashMap<Entity, usize> = agents
+        .0
+        .iter()
+        .filter(|entity| query.get(**entity).is_ok())
+        .enumerate()
+        .map(|(index, entity)| (*entity, index))
+        .collect();
+
+    let agents: Vec<AgentSnapshot> = recorded
+        .into_iter()
+        .map(
+            |(
+                transform,
+                Velocity(velocity),
+                Heading(heading),
+                PositionHistory(position_history),
+                behaviour,
+                friend_foe,
+                memory,
+                blind_state,
+                Target(target),
+                Speed(speed),
+                VisionRange(vision),
+                pinned,
+            )| AgentSnapshot {
+                behaviour: *behaviour,
+                position: transform.translation,
+                velocity: *velocity,
+                heading: *heading,
+                history: position_history.iter().copied().collect(),
+                relation: friend_foe.and_then(|FriendFoe(friend, foe)| {
+                    Some((*indices.get(friend)?, *indices.get(foe)?))
+                }),
+                memory: memory.clone(),
+                blind_state: blind_state.clone(),
+                target: *target,
+                speed: *speed,
+                vision: *vision,
+                pinned: pinned.is_some(),
+            },
+        )
+        .collect();
+
+    history.tick += 1;
+    let bytes = agents
+        .iter()
+        .fold(std::mem::size_of::<Snapshot>(), |bytes, agent| {
+            bytes
+                + std::mem::size_of::<AgentSnapshot>()
+                + agent.history.len() * std::mem::size_of::<(Vec3, Vec3)>()
+        });
+    let snapshot = Snapshot {
+        tick: history.tick,
+        rng: rng.0.clone(),
+        mean_deviation: stats.mean_deviation,
+        agents,
+        bytes,
+    };
+    history.snapshots.push_back(snapshot);
+
+    // the most recent state is always kept
+    let mut bytes: usize = history.snapshots.iter().map(|s| s.bytes).sum();
+    while history.snapshots.len() > 1
+        && (history.snapshots.len() > HISTORY_LENGTH || bytes > HISTORY_BYTES)
+    {
+        if let Some(oldest) = history.snapshots.pop_front() {
+            bytes -= oldest.bytes;
+        }
+    }
+}
+
+/// Restore the state of the simulation requested with [`SimulationHistory::restore`].
+fn restore_snapshot(
+    mut commands: Commands,
+    materials: Res<AgentMaterials>,
+    mut history: ResMut<SimulationHistory>,
+    mut stats: ResMut<SimStats>,
+    agents: Option<ResMut<Agents>>,
+    rng: Option<ResMut<SimRng>>,
+) {
+    let history = &mut *history;
+    let (index, mut agents, mut rng) = match (history.requested.take(), agents, rng) {
+        (Some(index), Some(agents), Some(rng)) => (index, agents, rng),
+        _ => return,
+    };
+    let snapshot = match history.snapshots.get(index) {
+        Some(snapshot) => snapshot,
+        None => return,
+    };
+
+    // the current agents are reused, the missing ones are spawned and the extra ones despawned
+    let kept = snapshot.agents.len().min(agents.0.len());
+    for entity in agents.0.split_off(kept) {
+        commands.entity(entity).despawn();
+    }
+    for agent in &snapshot.agents[kept..] {
+        let material = materials.get(&agent.behaviour);
+        let (x, y) = (agent.position.x, agent.position.y);
+        let entity = commands
+            .spawn_bundle(AgentBundle::new(material, x, y, agent.behaviour))
+            .id();
+        agents.0.push(entity);
+    }
+
+    for (entity, agent) in agents.0.iter().zip(&snapshot.agents) {
+        let mut entity_commands = commands.entity(*entity);
+        entity_commands.insert_bundle((
+            agent_transform(agent.position),
+            materials.get(&agent.behaviour),
+            agent.behaviour,
+            Velocity(agent.velocity),
+            Heading(agent.heading),
+            agent.memory.clone(),
+            agent.blind_state.clone(),
+            PositionHistory(agent.history.iter().copied().collect()),
+            Trail::default(),
+            Target(agent.target),
+            Speed(agent.speed),
+            VisionRange(agent.vision),
+        ));
+        match agent.relation {
+            Some((friend, foe)) => {
+                entity_commands.insert(FriendFoe(agents.0[friend], agents.0[foe]));
+            }
+            None => {
+                entity_commands.remove::<FriendFoe>();
+            }
+        }
+        if agent.pinned {
+            entity_commands.insert(Pinned);
+        } else {
+            entity_commands.remove::<Pinned>();
+        }
+    }
+
+    rng.0 = snapshot.rng.clone();
+    stats.mean_deviation = snapshot.mean_deviation;
+    history.tick = snapshot.tick;
+    history.current = Some(index);
+}
+
+/// Pick a random agent among `entities` that isn't `excluded`,
+/// `None` if there is no such agent.
+fn random_other(rng: &mut impl Rng, entities: &[Entity], excluded: &[Entity]) -> Option<Entity> {
+    if entities.iter().all(|e| excluded.contains(e)) {
+        return None;
+    }
+    loop {
+        let entity = entities[rng.gen_range(0..entities.len())];
+        if !excluded.contains(&entity) {
+            return Some(entity);
+        }
+    }
+}
+
+/// The components modified by [`edit_agents`].
+type EditedAgent<'a> = (
+    Entity,
+    &'a mut Transform,
+    &'a mut AgentBehaviour,
+    &'a mut Handle<ColorMaterial>,
+    Option<&'a mut FriendFoe>,
+    Option<&'a Pinned>,
+);
+
+/// Apply the [`EditAgent`] events.
+fn edit_agents(
+    mut commands: Commands,
+    mut events: EventReader<EditAgent>,
+    materials: Res<AgentMaterials>,
+    simulation_settings: Res<SimulationSettings>,
+    agents: Option<ResMut<Agents>>,
+    rng: Option<ResMut<SimRng>>,
+    mut query: Query<EditedAgent<'_>>,
+) {
+    let (mut agents, mut rng) = match (agents, rng) {
+        (Some(agents), Some(rng)) => (agents, rng),
+        _ => return,
+    };
+
+    for event in events.iter() {
+        match *event {
+            EditAgent::Move(entity, position) => {
+                if let Ok((_, mut transform, ..)) = query.get_mut(entity) {
+                    transform.translation = position;
+                }
+            }
+            EditAgent::SetPinned(entity, true) => {
+                commands.entity(entity).insert(Pinned);
+            }
+            EditAgent::SetPinned(entity, false) => {
+                commands.entity(entity).remove::<Pinned>();
+            }
+            EditAgent::SetKind(entity, new_behaviour) => {
+                if let Ok((_, _, mut behaviour, mut material, ..)) = query.get_mut(entity) {
+                    *behaviour = new_behaviour;
+                    *material = materials.get(&new_behaviour);
+                }
+            }
+            EditAgent::SetFriend(entity, other) | EditAgent::SetFoe(entity, other) => {
+                if entity == other || !agents.0.contains(&other) {
+                    continue;
+                }
+                let is_friend = matches!(event, EditAgent::SetFriend(..));
+                let mut friend_foe = match query.get_mut(entity) {
+                    Ok((.., friend_foe, _)) => friend_foe,
+                    Err(_) => continue,
+                };
+
+                let relation = match (friend_foe.as_deref(), is_friend) {
+                    // the friend and the foe are swapped to stay different
+                    (Some(&FriendFoe(friend, foe)), true) => {
+                        FriendFoe(other, if foe == other { friend } else { foe })
+                    }
+                    (Some(&FriendFoe(friend, foe)), false) => {
+                        FriendFoe(if friend == other { foe } else { friend }, other)
+                    }
+                    // an agent without relations gets a random other one
+                    (None, _) => match random_other(&mut rng.0, &agents.0, &[entity, other]) {
+                        Some(third) if is_friend => FriendFoe(other, third),
+                        Some(third) => FriendFoe(third, other),
+                        None => continue,
+                    },
+                };
+                match friend_foe.as_deref_mut() {
+                    Some(friend_foe) => *friend_foe = relation,
+                    None => {
+                        commands.entity(entity).insert(relation);
+                    }
+                }
+            }
+            EditAgent::Paint {
+                center,
+                radius,
+                density,
+                behaviour,
+            } => {
+                let area = std::f32::consts::PI * radius.powi(2);
+                let wanted = (density * area / 10_000.0).round() as usize;
+                let present = query
+                    .iter_mut()
+                    .filter(|(_, transform, ..)| {
+                        transform.translation.xy().distance(center) <= radius
+                    })
+                    .count();
+
+                let rng = &mut rng.0;
+                let kind = simulation_settings.kind(&behaviour);
+                for _ in present..wanted {
+                    // uniform distribution over the disk
+                    let distance = radius * rng.gen::<f32>().sqrt();
+                    let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+                    let position = center + Vec2::new(angle.cos(), angle.sin()) * distance;
+                    let entity = commands
+                        .spawn_bundle(AgentBundle::new(
+                            materials.get(&behaviour),
+                            position.x,
+                            position.y,
+                            behaviour,
+                        ))
+                        .id();
+
+                    let friend = random_other(rng, &agents.0, &[entity]);
+                    let foe =
+                        friend.and_then(|friend| random_other(rng, &agents.0, &[entity, friend]));
+                    if let (Some(friend), Some(foe)) = (friend, foe) {
+                        commands.entity(entity).insert(FriendFoe(friend, foe));
+                    }
+                    let a = rng.gen_range(0.0..std::f32::consts::TAU);
+                    commands.entity(entity).insert_bundle((
+                        Heading(Vec3::new(a.cos(), a.sin(), 0.0)),
+                        Speed(kind.speed.sample(rng)),
+                        VisionRange(kind.vision.sample(rng)),
+                    ));
+                    agents.0.push(entity);
+                }
+            }
+            EditAgent::Delete(entity) => {
+                if !agents.0.contains(&entity) {
+                    continue;
+                }
+                agents.0.retain(|e| *e != entity);
+                commands.entity(entity).despawn();
+
+                // the agents related to the deleted one choose another friend or foe,
+                // they lose their relations if there is no other agent left.
+                for (other, .., friend_foe, _) in query.iter_mut() {
+                    let mut friend_foe = match friend_foe {
+                        Some(friend_foe) if other != entity => friend_foe,
+                        _ => continue,
+                    };
+                    let FriendFoe(friend, foe) = *friend_foe;
+                    if friend == entity {
+                        match random_other(&mut rng.0, &agents.0, &[other, foe]) {
+                            Some(friend) => friend_foe.0 = friend,
+                            None => {
+                                commands.entity(other).remove::<FriendFoe>();
+                            }
+                        }
+                    } else if foe == entity {
+                        match random_other(&mut rng.0, &agents.0, &[other, friend]) {
+                            Some(foe) => friend_foe.1 = foe,
+                            None => {
+                                commands.entity(other).remove::<FriendFoe>();
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Fill `grid` with the given agents, with the radius of their kind.
+fn fill_grid<'a>(
+    grid: &mut SpatialGrid,
+    simulation_settings: &SimulationSettings,
+    agents: impl Iterator<Item = (Entity, Vec2, &'a AgentBehaviour)>,
+) {
+    let max_radius = simulation_settings
+        .heroe
+        .radius
+        .max(simulation_settings.coward.radius);
+    grid.clear(4.0 * max_radius);
+    for (entity, position, behaviour) in agents {
+        grid.insert(entity, position, simulation_settings.kind(behaviour).radius);
+    }
+}
+
+/// Direction to push `entity` away from `other`, `offset` being the vector from `other` to `entity`.
+/// Agents at the exact same position are pushed in opposite directions based on their ids.
+fn push_direction(entity: Entity, other: Entity, offset: Vec2) -> Vec2 {
+    if offset != Vec2::ZERO {
+        return offset.normalize();
+    }
+    let (first, second) = if entity.id() < other.id() {
+        (entity, other)
+    } else {
+        (other, entity)
+    };
+    let a = first.id() as f32 * 2.4 + second.id() as f32 * 0.7;
+    let direction = Vec2::new(a.cos(), a.sin());
+    if entity == first {
+        direction
+    } else {
+        -direction
+    }
+}
+
+/// Fill the [`SpatialGrid`] with the current position of the agents.
+fn update_spatial_grid(
+    simulation_settings: Res<SimulationSettings>,
+    mut grid: ResMut<SpatialGrid>,
+    agents: Query<(Entity, &Transform, &AgentBehaviour), With<Agent>>,
+) {
+    fill_grid(
+        &mut grid,
+        &simulation_settings,
+        agents
+            .iter()
+            .map(|(entity, transform, behaviour)| (entity, transform.translation.xy(), behaviour)),
+    );
+}
+
+/// Record the position and the velocity of the agents in their [`PositionHistory`].
+fn record_positions(
+    simulation_settings: Res<SimulationSettings>,
+    mut agents: Query<(&Transform, &Velocity, &mut PositionHistory), With<Agent>>,
+) {
+    for (transform, velocity, mut history) in agents.iter_mut() {
+        history.0.push_front((transform.translation, velocity.0));
+        history.0.truncate(simulation_settings.perception_delay + 1);
+    }
+}
+
+/// Record the position of the agents in their [`Trail`].
+fn record_trails(
+    debug_settings: Res<SimulationDebug>,
+    mut agents: Query<(&Transform, &mut Trail), With<Agent>>,
+) {
+    for (transform, mut trail) in agents.iter_mut() {
+        trail.0.push_front(transform.translation);
+        trail.0.truncate(debug_settings.trail_length);
+    }
+}
+
+/// Count the agents in each cell of the [`Heatmap`].
+fn record_heatmap(
+    simulation_settings: Res<SimulationSettings>,
+    mut heatmap: ResMut<Heatmap>,
+    agents: Query<(&Transform, &AgentBehaviour), With<Agent>>,
+) {
+    const CELLS: usize = HEATMAP_RESOLUTION * HEATMAP_RESOLUTION;
+    let cell_size = 2.0 * simulation_settings.arena_size / HEATMAP_RESOLUTION as f32;
+
+    let heatmap = &mut *heatmap;
+    heatmap.current = vec![[0; 2]; CELLS];
+    heatmap.total.resize(CELLS, [0; 2]);
+    heatmap.ticks += 1;
+
+    for (transform, behaviour) in agents.iter() {
+        let cell =
+            (transform.translation.xy() + Vec2::splat(simulation_settings.arena_size)) / cell_size;
+        let column = (cell.x as usize).min(HEATMAP_RESOLUTION - 1);
+        let row = (cell.y as usize).min(HEATMAP_RESOLUTION - 1);
+        let kind = match behaviour {
+            AgentBehaviour::Heroe => 0,
+            AgentBehaviour::Coward => 1,
+        };
+        heatmap.current[row * HEATMAP_RESOLUTION + column][kind] += 1;
+    }
+    for (total, current) in heatmap.total.iter_mut().zip(&heatmap.current) {
+        total[0] += current[0] as u64;
+        total[1] += current[1] as u64;
+    }
+}
+
+/// Update agents velocity component, with the motion noise.
 fn update_agents(
     time: Res<Time>,
     simulation_settings: Res<SimulationSettings>,
-    simulation_speed: Res<SimulationSpeed>,
+    (simulation_speed, temperature): (Res<SimulationSpeed>, Res<SimulationTemperature>),
     mut rng: ResMut<SimRng>,
-    agents: Query<(&Transform, &AgentBehaviour, &FriendFoe)>,
-    mut velocities: Query<
-        (Entity, &mut Velocity),
-        (With<Transform>, With<AgentBehaviour>, With<FriendFoe>),
-    >,
+    mut grid: ResMut<SpatialGrid>,
+    agent_list: Res<Agents>,
+    mut agents: Query<(
+        Entity,
+        &Transform,
+        &Heading,
+        &AgentBehaviour,
+        &FriendFoe,
+        &PositionHistory,
+        &Speed,
+        &VisionRange,
+        &mut Velocity,
+        &mut Memory,
+        &mut BlindState,
+        &mut Sight,
+        &mut Target,
+        &mut Intent,
+        Option<&Pinned>,
+    )>,
 ) {
-    for (entity, mut velocity) in velocities.iter_mut() {
-        let (transform, behaviour, FriendFoe(friend, foe)) = agents.get(entity).unwrap();
-        let friend_pos = agents.get(*friend).unwrap().0.translation;
-        let foe_pos = agents.get(*foe).unwrap().0.translation;
-
-        // compute a velocity vector to fiend and to (or from) foe
-        // based on the bahaviour of the agent.
-        let (to_friend, to_foe) = match behaviour {
-            // move toward its friend and its foe
-            AgentBehaviour::Heroe => (
-                friend_pos - transform.translation,
-                foe_pos - transform.translation,
-            ),
-            // move in the direction of its friend and
-            // in the opposite direction of its foe
-            AgentBehaviour::Coward => (
-                friend_pos - transform.translation,
-                transform.translation - foe_pos,
-            ),
+    let half_fov_cos = (simulation_settings.field_of_view.to_radians() / 2.0).cos();
+    let delay = simulation_settings.perception_delay;
+    let sequential = simulation_settings.update_scheme != UpdateScheme::Synchronous;
+    let noise_amplitude = motion_noise_amplitude(
+        temperature.0,
+        time.delta_seconds(),
+        simulation_settings.use_steering,
+    );
+
+    // what the agents see of each other: their state at the beginning of the frame,
+    // updated after each move with the sequential schemes.
+    let mut observed: HashMap<Entity, Observed> = agents
+        .iter_mut()
+        .map(
+            |(entity, transform, _, _, _, history, _, _, velocity, ..)| {
+                (
+                    entity,
+                    Observed {
+                        position: transform.translation,
+                        velocity: velocity.0,
+                        delayed: history
+                            .get(delay)
+                            .unwrap_or((transform.translation, velocity.0)),
+                    },
+                )
+            },
+        )
+        .collect();
+
+    let mut order = agent_list.0.clone();
+    if simulation_settings.update_scheme == UpdateScheme::RandomSequential {
+        order.shuffle(&mut rng.0);
+    }
+
+    for entity in order {
+        let (
+            _,
+            transform,
+            Heading(heading),
+            behaviour,
+            FriendFoe(friend, foe),
+            _,
+            Speed(speed),
+            VisionRange(vision),
+            mut velocity,
+            mut memory,
+            mut blind_state,
+            mut sight,
+            mut target,
+            mut intent,
+            pinned,
+        ) = match agents.get_mut(entity) {
+            Ok(agent) => agent,
+            Err(_) => continue,
+        };
+
+        // the positions of the friend and the foe as perceived by the agent:
+        // delayed and noisy.
+        let (friend_observed, foe_observed) = match (observed.get(friend), observed.get(foe)) {
+            (Some(friend), Some(foe)) => (friend, foe),
+            // the friend or the foe has been deleted, the agent has nowhere to go
+            _ => {
+                *intent = Intent::default();
+                target.0 = None;
+                velocity.0 = Vec3::ZERO;
+                continue;
+            }
+        };
+        // the position and the velocity come from the same delayed state
+        let mut perceive = |target: &Observed| {
+            let (position, velocity) = target.perceived(delay);
+            if simulation_settings.perception_noise > 0.0 {
+                let rng = &mut rng.0;
+                let noise = Vec3::new(standard_normal(rng), standard_normal(rng), 0.0);
+                (
+                    position + noise * simulation_settings.perception_noise,
+                    velocity,
+                )
+            } else {
+                (position, velocity)
+            }
+        };
+        let (friend_pos, _) = perceive(friend_observed);
+        let (foe_pos, foe_velocity) = perceive(foe_observed);
+        // where the agent expect its foe to be
+        let aimed_foe_pos = if simulation_settings.use_anticipation {
+            foe_pos + foe_velocity * simulation_settings.anticipation_horizon
+        } else {
+            foe_pos
+        };
+
+        let kind = simulation_settings.kind(behaviour);
+        let speed = simulation_speed.0 * kind.speed_multiplier * speed;
+        let vision_range = if kind.use_vision_limit {
+            *vision
+        } else {
+            f32::INFINITY
         };
 
-        let desired_velocity = if simulation_settings.use_vision_limit {
-            let can_see_friend = to_friend.length() < simulation_settings.vision_limit;
-            let can_see_foe = to_foe.length() < simulation_settings.vision_limit;
-            match (can_see_friend, can_see_foe) {
-                (true, true) => to_friend + to_foe,
-                (true, false) => to_friend,
-                (false, true) => to_foe,
-                (false, false) => match simulation_settings.blind_behaviour {
-                    BlindBehavour::NoMove => Vec3::ZERO,
-                    BlindBehavour::RandomMove => {
+        // an agent see its target if it's in its vision range, in front of it
+        // and if no other agent stand between them.
+        let position = transform.translation.xy();
+        let can_see = |target: Entity, target_pos: Vec3| {
+            let in_range = position.distance(target_pos.xy()) < vision_range;
+            let in_field_of_view = !simulation_settings.use_field_of_view
+                || heading
+                    .xy()
+                    .dot((target_pos.xy() - position).normalize_or_zero())
+                    >= half_fov_cos;
+            in_range
+                && in_field_of_view
+                && !(simulation_settings.use_occlusion
+                    && grid
+                        .any_on_segment(position, target_pos.xy(), |e| e != entity && e != target))
+        };
+        let can_see_friend = can_see(*friend, friend_pos);
+        let can_see_foe = can_see(*foe, foe_pos);
+        sight.friend = can_see_friend;
+        sight.foe = can_see_foe;
+
+        // remember where the friend and the foe have been seen
+        memory.age(time.delta_seconds(), simulation_settings.memory_duration);
+        if can_see_friend {
+            memory.friend = Some((friend_pos, 0.0));
+        }
+        if can_see_foe {
+            memory.foe = Some((foe_pos, 0.0));
+        }
+
+        if can_see_friend || can_see_foe {
+            blind_state.blind_time = 0.0;
+        } else {
+            if blind_state.blind_time == 0.0 {
+                blind_state.spiral_phase = heading.y.atan2(heading.x);
+            }
+            blind_state.blind_time += time.delta_seconds();
+        }
+
+        let mut random_move = || {
+            let rng = &mut rng.0;
+            let a = rng.gen_range(0.0..std::f32::consts::TAU);
+
+            velocity
+                .0
+                .lerp(Vec3::new(a.cos(), a.sin(), 0.0), time.delta_seconds())
+        };
+
+        // with the target point model, the move is toward a point
+        // defined by the positions of the friend and the foe.
+        let target_point = if simulation_settings.movement_model == MovementModel::TargetPoint {
+            Some(match behaviour {
+                AgentBehaviour::Heroe => {
+                    friend_pos.lerp(aimed_foe_pos, simulation_settings.heroe_fraction)
+                }
+                AgentBehaviour::Coward => {
+                    friend_pos
+                        + (friend_pos - aimed_foe_pos).normalize_or_zero()
+                            * simulation_settings.hiding_distance
+                }
+            })
+        } else {
+            None
+        };
+
+        let foe_weight = behaviour.foe_weight(kind.foe_weight);
+
+        intent.points.clear();
+        intent.direction = None;
+        match (can_see_friend, can_see_foe) {
+            (true, true) => match target_point {
+                Some(point) => intent.points.push((point, 1.0, false)),
+                None => {
+                    intent.points.push((friend_pos, kind.friend_weight, true));
+                    intent.points.push((aimed_foe_pos, foe_weight, true));
+                }
+            },
+            (true, false) => intent.points.push((friend_pos, kind.friend_weight, true)),
+            (false, true) => intent.points.push((aimed_foe_pos, foe_weight, true)),
+            (false, false) => {
+                // move based on the last known positions, the missing ones are ignored.
+                let remembered = [
+                    memory
+                        .friend
+                        .map(|(pos, _)| (pos, kind.friend_weight, true)),
+                    memory.foe.map(|(pos, _)| (pos, foe_weight, true)),
+                ];
+
+                match kind.blind_behaviour {
+                    BlindBehavour::NoMove => {}
+                    BlindBehavour::RandomMove => intent.direction = Some(random_move()),
+                    BlindBehavour::Memory => intent.points.extend(remembered.iter().flatten()),
+                    BlindBehavour::MemoryThenRandom => {
+                        if remembered.iter().any(Option::is_some) {
+                            intent.points.extend(remembered.iter().flatten());
+                        } else {
+                            intent.direction = Some(random_move());
+                        }
+                    }
+                    BlindBehavour::Wander => {
                         let rng = &mut rng.0;
-                        let a = rng.gen_range(0.0..std::f32::consts::TAU);
+                        let jitter = simulation_settings.wander_jitter * time.delta_seconds();
+                        let displacement =
+                            Vec3::new(rng.gen_range(-1.0..=1.0), rng.gen_range(-1.0..=1.0), 0.0)
+                                * jitter;
+                        let wander = (blind_state.wander + displacement).normalize_or_zero();
+                        blind_state.wander = if wander == Vec3::ZERO {
+                            *heading
+                        } else {
+                            wander
+                        } * simulation_settings.wander_radius;
 
-                        velocity
-                            .0
-                            .lerp(Vec3::new(a.cos(), a.sin(), 0.0), time.delta_seconds())
+                        intent.direction = Some(
+                            *heading * simulation_settings.wander_distance + blind_state.wander,
+                        );
                     }
-                },
+                    BlindBehavour::ReturnToCenter => intent.points.push((Vec3::ZERO, 1.0, false)),
+                    BlindBehavour::FollowNearest => {
+                        if let Some((_, pos)) = grid.nearest(position, vision_range, |e, pos| {
+                            e != entity && can_see(e, pos.extend(0.0))
+                        }) {
+                            intent.points.push((pos.extend(0.0), 1.0, false));
+                        }
+                    }
+                    BlindBehavour::SpiralSearch => {
+                        // archimedean spiral `r = b * theta` travelled at constant speed,
+                        // `theta` is derived from the travelled distance `s = b * theta² / 2`.
+                        let b = simulation_settings.spiral_spacing / std::f32::consts::TAU;
+                        let travelled = speed * blind_state.blind_time;
+                        let theta = (2.0 * travelled / b).sqrt();
+                        // the spiral is turned to start in the direction the agent was heading
+                        let angle = theta + blind_state.spiral_phase;
+                        intent.direction = Some(Vec3::new(
+                            angle.cos() - theta * angle.sin(),
+                            angle.sin() + theta * angle.cos(),
+                            0.0,
+                        ));
+                    }
+                }
             }
+        }
+
+        // push the agent away from the agents it overlaps
+        let separation = if simulation_settings.collision == CollisionMode::Separation {
+            let push = grid
+                .within(position, kind.radius + grid.max_radius())
+                .filter(|(e, _, _)| *e != entity)
+                .fold(Vec2::ZERO, |push, (other, other_pos, other_radius)| {
+                    let min_distance = kind.radius + other_radius;
+                    let offset = position - other_pos;
+                    let overlap = (min_distance - offset.length()).max(0.0) / min_distance;
+                    push + push_direction(entity, other, offset) * overlap
+                });
+            push.extend(0.0) * kind.separation * speed
         } else {
-            to_friend + to_foe
+            Vec3::ZERO
         };
 
-        velocity.0 = desired_velocity.normalize_or_zero() * simulation_speed.0;
+        intent.speed = speed;
+        intent.separation = separation;
+        target.0 = intent
+            .offset(transform.translation)
+            .map(|offset| transform.translation + offset);
+
+        // with the steering model, the velocity is updated during the move
+        if !simulation_settings.use_steering {
+            velocity.0 = intent.desired_velocity(transform.translation, &simulation_settings);
+        }
+
+        intent.noise = match noise_amplitude {
+            Some(amplitude) if pinned.is_none() => {
+                let rng = &mut rng.0;
+                Vec3::new(standard_normal(rng), standard_normal(rng), 0.0) * amplitude * speed
+            }
+            _ => Vec3::ZERO,
+        };
+        velocity.0 += intent.noise;
+
+        // with the sequential schemes, the next agents see this one after its move
+        // (the collisions are resolved later, see `UpdateScheme`)
+        if sequential && pinned.is_none() {
+            let (new_position, new_velocity) = intent.integrate(
+                transform.translation,
+                velocity.0,
+                time.delta_seconds(),
+                &simulation_settings,
+            );
+            let new_position = clamp_to_arena(new_position, simulation_settings.arena_size);
+            grid.move_entity(entity, position, new_position.xy());
+            if let Some(observed) = observed.get_mut(&entity) {
+                observed.position = new_position;
+                observed.velocity = new_velocity;
+            }
+        }
+    }
+}
+
+/// Move the agents that aren't [`Pinned`] according to their [`Intent`] and their [`Velocity`],
+/// and measure the kinetic energy, the overshoot and the effect of the motion noise on the move.
+/// The agents without [`FriendFoe`] (when too few agents remain) stay still.
+fn move_agents(
+    time: Res<Time>,
+    simulation_settings: Res<SimulationSettings>,
+    mut stats: ResMut<SimStats>,
+    mut agents: Query<(&mut Transform, &mut Velocity, &Intent, Option<&Pinned>), With<FriendFoe>>,
+) {
+    let delta = time.delta_seconds();
+    let mass = if simulation_settings.use_steering {
+        simulation_settings.steering.mass
+    } else {
+        1.0
+    };
+
+    let mut energy = 0.0;
+    let (mut moving, mut reversed) = (0u32, 0u32);
+    let (mut moved, mut shift) = (0u32, 0.0);
+    for (mut transform, mut velocity, intent, pinned) in agents.iter_mut() {
+        if pinned.is_some() {
+            velocity.0 = Vec3::ZERO;
+            continue;
+        }
+
+        let start = transform.translation;
+        let (position, new_velocity) =
+            intent.integrate(start, velocity.0, delta, &simulation_settings);
+        moved += 1;
+        if intent.noise != Vec3::ZERO {
+            let (quiet, _) = intent.integrate(
+                start,
+                velocity.0 - intent.noise,
+                delta,
+                &simulation_settings,
+            );
+            shift += position.distance(quiet);
+        }
+        transform.translation = position;
+        velocity.0 = new_velocity;
+        energy += 0.5 * mass * new_velocity.length_squared();
+
+        // an agent whose desired direction reversed went past the point where it wanted to go
+        let before = intent.direction(start, &simulation_settings);
+        if before != Vec3::ZERO {
+            moving += 1;
+            if before.dot(intent.direction(position, &simulation_settings)) < 0.0 {
+                reversed += 1;
+            }
+        }
+    }
+
+    stats.kinetic_energy = energy;
+    stats.noise_shift = if moved == 0 {
+        0.0
+    } else {
+        shift / moved as f32
+    };
+    stats.overshoot = if moving == 0 {
+        0.0
+    } else {
+        reversed as f32 / moving as f32
+    };
+}
+
+/// Move overlapping agents apart when [`CollisionMode::Resolve`] is used.
+fn resolve_collisions(
+    simulation_settings: Res<SimulationSettings>,
+    mut agents: Query<(Entity, &mut Transform, &AgentBehaviour), With<Agent>>,
+    pinned: Query<Entity, (With<Agent>, With<Pinned>)>,
+) {
+    if simulation_settings.collision != CollisionMode::Resolve {
+        return;
     }
+    let pinned: HashSet<Entity> = pinned.iter().collect();
+
+    // the grid is built from the positions after this frame's move
+    let mut grid = SpatialGrid::default();
+    fill_grid(
+        &mut grid,
+        &simulation_settings,
+        agents
+            .iter_mut()
+            .map(|(entity, transform, behaviour)| (entity, transform.translation.xy(), behaviour)),
+    );
+
+    let mut corrections = Vec::new();
+    for (entity, transform, behaviour) in agents.iter_mut() {
+        // pinned agents don't move, their partner does the whole way
+        if pinned.contains(&entity) {
+            continue;
+        }
+        let position = transform.translation.xy();
+        let radius = simulation_settings.kind(behaviour).radius;
+        let correction = grid
+            .within(position, radius + grid.max_radius())
+            .filter(|(e, _, _)| *e != entity)
+            .fold(
+                Vec2::ZERO,
+                |correction, (other, other_pos, other_radius)| {
+                    let offset = position - other_pos;
+                    let overlap = (radius + other_radius - offset.length()).max(0.0);
+                    // each agent of the pair does half of the way
+                    let share = if pinned.contains(&other) { 1.0 } else { 0.5 };
+                    correction + push_direction(entity, other, offset) * overlap * share
+                },
+            );
+        if correction != Vec2::ZERO {
+            corrections.push((entity, correction));
+        }
+    }
+
+    for (entity, correction) in corrections {
+        if let Ok((_, mut transform, _)) = agents.get_mut(entity) {
+            transform.translation += correction.extend(0.0);
+        }
+    }
+}
+
+/// Amplitude of the random perturbation of the velocity of the agents relative to their speed,
+/// based on the [`SimulationTemperature`], `None` without perturbation.
+/// The perturbation is a Wiener increment so its effect doesn't depend on the frame rate:
+/// with the steering model, the velocity diffuses (the kick is `sqrt(2 T dt)`), otherwise the
+/// velocity is decided again each tick so the position diffuses (the kick is `sqrt(2 T / dt)`,
+/// moving the agent by `sqrt(2 T dt)` during the tick). This kick grows without bound with
+/// short frames, so the velocities are displayed without it.
+fn motion_noise_amplitude(temperature: f32, delta: f32, use_steering: bool) -> Option<f32> {
+    if temperature <= 0.0 || delta <= 0.0 {
+        None
+    } else if use_steering {
+        Some((2.0 * temperature * delta).sqrt())
+    } else {
+        Some((2.0 * temperature / delta).sqrt())
+    }
+}
+
+/// The position closest to `position` in an arena of half size `arena_size`.
+fn clamp_to_arena(position: Vec3, arena_size: f32) -> Vec3 {
+    position
+        .max(Vec3::new(-arena_size, -arena_size, 0.0))
+        .min(Vec3::new(arena_size, arena_size, 0.0))
 }
 
-/// This system ensure agents don't move out the arena.
+/// This system ensure agents don't move out the arena. Pinned agents stay where they were put.
 fn keep_in_arena(
     simulation_settings: Res<SimulationSettings>,
-    mut agents: Query<&mut Transform, With<AgentBehaviour>>,
+    mut agents: Query<&mut Transform, (With<AgentBehaviour>, Without<Pinned>)>,
 ) {
     for mut transform in agents.iter_mut() {
-        transform.translation = transform
-            .translation
-            .max(Vec3::new(
-                -simulation_settings.arena_size,
-                -simulation_settings.arena_size,
-                0.0,
-            ))
-            .min(Vec3::new(
-                simulation_settings.arena_size,
-                simulation_settings.arena_size,
-                0.0,
-            ));
+        transform.translation =
+            clamp_to_arena(transform.translation, simulation_settings.arena_size);
     }
 }
 
@@ -373,8 +2290,10 @@ fn display_lines(
     const ARROW_POS_OFFSET: f32 = 10.0;
     if settings.display_friend_links || settings.display_foe_links {
         for (transform, FriendFoe(friend, foe)) in agents.iter() {
-            let friend_pos = agents.get(*friend).unwrap().0.translation;
-            let foe_pos = agents.get(*foe).unwrap().0.translation;
+            let (friend_pos, foe_pos) = match (agents.get(*friend), agents.get(*foe)) {
+                (Ok((friend, _)), Ok((foe, _))) => (friend.translation, foe.translation),
+                _ => continue,
+            };
             let pos = transform.translation;
 
             if settings.display_friend_links {
@@ -434,12 +2353,418 @@ fn display_center_of_mass(
         );
     }
 }
-fn compute_stats(mut stats: ResMut<SimStats>, agents: Query<&Transform, With<Agent>>) {
+
+/// Display the vision of the [`SelectedAgent`].
+fn display_vision_cone(
+    settings: Res<SimulationDebug>,
+    simulation_settings: Res<SimulationSettings>,
+    selected_agent: Res<SelectedAgent>,
+    mut lines: ResMut<DebugLines>,
+    agents: Query<(&Transform, &Heading, &VisionRange, &AgentBehaviour), With<Agent>>,
+) {
+    if !settings.vision_cone {
+        return;
+    }
+
+    if let Some((transform, Heading(heading), VisionRange(vision), behaviour)) =
+        selected_agent.0.and_then(|e| agents.get(e).ok())
+    {
+        let color = Color::rgb(0.9, 0.8, 0.2);
+        let pos = transform.translation;
+        // without vision limit, the cone goes across the whole arena
+        let range = if simulation_settings.kind(behaviour).use_vision_limit {
+            *vision
+        } else {
+            4.0 * simulation_settings.arena_size
+        };
+
+        if simulation_settings.use_field_of_view {
+            let half_fov = simulation_settings.field_of_view.to_radians() / 2.0;
+            let angle = heading.y.atan2(heading.x);
+            let left = Quat::from_rotation_z(half_fov) * *heading * range;
+            let right = Quat::from_rotation_z(-half_fov) * *heading * range;
+
+            lines.line_colored(pos, pos + left, 0.0, color);
+            lines.line_colored(pos, pos + right, 0.0, color);
+            lines.arc_colored(pos, range, (angle - half_fov, angle + half_fov), 0.0, color);
+        } else {
+            lines.circle_colored(pos, range, 0.0, color);
+        }
+    }
+}
+
+/// Highlight the [`SelectedAgent`], its friend and its foe.
+fn display_selection(
+    simulation_settings: Res<SimulationSettings>,
+    selected_agent: Res<SelectedAgent>,
+    mut lines: ResMut<DebugLines>,
+    agents: Query<(&Transform, &AgentBehaviour, &FriendFoe), With<Agent>>,
+) {
+    const MARGIN: f32 = 4.0;
+    let selected = match selected_agent.0 {
+        Some(selected) => selected,
+        None => return,
+    };
+
+    if let Ok((_, _, FriendFoe(friend, foe))) = agents.get(selected) {
+        for (entity, color) in [
+            (selected, Color::WHITE),
+            (*friend, Color::GREEN),
+            (*foe, Color::RED),
+        ] {
+            if let Ok((transform, behaviour, _)) = agents.get(entity) {
+                let radius = simulation_settings.kind(behaviour).radius + MARGIN;
+                lines.circle_colored(transform.translation, radius, 0.0, color);
+            }
+        }
+    }
+}
+
+/// Display the point each agent is moving to.
+fn display_target_points(
+    settings: Res<SimulationDebug>,
+    selected_agent: Res<SelectedAgent>,
+    mut lines: ResMut<DebugLines>,
+    agents: Query<(Entity, &Transform, &Target, &AgentBehaviour), With<Agent>>,
+) {
+    if settings.target_points == OverlayScope::Hidden {
+        return;
+    }
+
+    for (entity, transform, Target(target), behaviour) in agents.iter() {
+        if !settings.target_points.shows(entity, &selected_agent) {
+            continue;
+        }
+        if let Some(target) = target {
+            let color = kind_color(behaviour);
+            lines.line_colored(transform.translation, *target, 0.0, color * 0.5);
+            lines.cross_colored(*target, 4.0, 0.0, color);
+        }
+    }
+}
+
+/// Display the velocity of the agents, as the move during one second.
+fn display_velocities(
+    settings: Res<SimulationDebug>,
+    simulation_settings: Res<SimulationSettings>,
+    selected_agent: Res<SelectedAgent>,
+    mut lines: ResMut<DebugLines>,
+    agents: Query<(Entity, &Transform, &Velocity, &Intent), With<Agent>>,
+) {
+    if settings.velocities == OverlayScope::Hidden {
+        return;
+    }
+
+    for (entity, transform, Velocity(velocity), intent) in agents.iter() {
+        // without steering, the kick of the motion noise only makes sense over the whole tick
+        let velocity = if simulation_settings.use_steering {
+            *velocity
+        } else {
+            *velocity - intent.noise
+        };
+        if settings.velocities.shows(entity, &selected_agent) && velocity != Vec3::ZERO {
+            let pos = transform.translation;
+            lines.arrow_colored(pos, pos + velocity, 0.0, Color::rgb(0.8, 0.8, 0.8));
+        }
+    }
+}
+
+/// Display the vision range of the agents whose kind has a vision limit.
+fn display_vision_ranges(
+    settings: Res<SimulationDebug>,
+    simulation_settings: Res<SimulationSettings>,
+    selected_agent: Res<SelectedAgent>,
+    mut lines: ResMut<DebugLines>,
+    agents: Query<(Entity, &Transform, &VisionRange, &AgentBehaviour), With<Agent>>,
+) {
+    if settings.vision_ranges == OverlayScope::Hidden {
+        return;
+    }
+
+    for (entity, transform, VisionRange(vision), behaviour) in agents.iter() {
+        if settings.vision_ranges.shows(entity, &selected_agent)
+            && simulation_settings.kind(behaviour).use_vision_limit
+        {
+            let color = kind_color(behaviour);
+            lines.circle_colored(transform.translation, *vision, 0.0, color * 0.5);
+        }
+    }
+}
+
+/// Display the limits of the arena the agents are kept in.
+fn display_arena_bounds(
+    settings: Res<SimulationDebug>,
+    simulation_settings: Res<SimulationSettings>,
+    mut lines: ResMut<DebugLines>,
+) {
+    if !settings.arena_bounds {
+        return;
+    }
+
+    let size = simulation_settings.arena_size;
+    let corners = [
+        Vec3::new(-size, -size, 0.0),
+        Vec3::new(size, -size, 0.0),
+        Vec3::new(size, size, 0.0),
+        Vec3::new(-size, size, 0.0),
+    ];
+    for (start, end) in corners.iter().zip(corners.iter().cycle().skip(1)) {
+        lines.line_colored(*start, *end, 0.0, Color::WHITE);
+    }
+}
+
+/// Display the convex hulls computed in [`SimStats`].
+fn display_hulls(
+    settings: Res<SimulationDebug>,
+    stats: Res<SimStats>,
+    mut lines: ResMut<DebugLines>,
+) {
+    if !settings.hulls {
+        return;
+    }
+
+    for (hull, color) in [
+        (&stats.heroe_hull, kind_color(&AgentBehaviour::Heroe)),
+        (&stats.coward_hull, kind_color(&AgentBehaviour::Coward)),
+        (&stats.hull, Color::rgb(0.8, 0.8, 0.8)),
+    ] {
+        let points = &hull.points;
+        for (start, end) in points.iter().zip(points.iter().cycle().skip(1)) {
+            lines.line_colored(start.extend(0.0), end.extend(0.0), 0.0, color);
+        }
+    }
+}
+
+/// Maximum number of lines used by the trails, the other overlays share the rest of [`MAX_LINES`].
+const TRAIL_LINES: usize = MAX_LINES / 2;
+
+/// Display the trail of the agents, fading with age.
+/// The oldest segments are skipped to keep the total under [`TRAIL_LINES`].
+fn display_trails(
+    settings: Res<SimulationDebug>,
+    selected_agent: Res<SelectedAgent>,
+    mut lines: ResMut<DebugLines>,
+    agents: Query<(Entity, &Trail, &AgentBehaviour), With<Agent>>,
+) {
+    if !settings.trails && !settings.selected_trail {
+        return;
+    }
+
+    let shown = if settings.trails {
+        agents.iter().count()
+    } else {
+        1
+    };
+    let segments = settings
+        .trail_length
+        .saturating_sub(1)
+        .min(TRAIL_LINES / shown.max(1));
+
+    for (entity, Trail(trail), behaviour) in agents.iter() {
+        if !settings.trails && selected_agent.0 != Some(entity) {
+            continue;
+        }
+
+        let color = kind_color(behaviour);
+        let faded = |age: usize| {
+            let mut color = color;
+            color.set_a(1.0 - age as f32 / segments as f32);
+            color
+        };
+        for (age, (start, end)) in trail
+            .iter()
+            .zip(trail.iter().skip(1))
+            .take(segments)
+            .enumerate()
+        {
+            lines.line_gradient(*start, *end, 0.0, faded(age), faded(age + 1));
+        }
+    }
+}
+
+/// Display the [`Heatmap`] under the agents.
+fn display_heatmap(
+    settings: Res<SimulationDebug>,
+    simulation_settings: Res<SimulationSettings>,
+    heatmap: Res<Heatmap>,
+    mut textures: ResMut<Assets<Texture>>,
+    materials: Res<Assets<ColorMaterial>>,
+    mut sprite: Query<(&mut Visible, &mut Transform, &Handle<ColorMaterial>), With<HeatmapSprite>>,
+) {
+    let (mut visible, mut transform, material) = sprite.single_mut().unwrap();
+    visible.is_visible = settings.heatmap;
+    if !settings.heatmap || !(settings.is_changed() || heatmap.is_changed()) {
+        return;
+    }
+
+    // the texture has one pixel per cell and covers the arena
+    transform.scale = Vec3::splat(2.0 * simulation_settings.arena_size / HEATMAP_RESOLUTION as f32);
+
+    let texture = materials
+        .get(material)
+        .and_then(|material| material.texture.as_ref())
+        .and_then(|texture| textures.get_mut(texture));
+    if let Some(texture) = texture {
+        texture.data = heatmap.image(settings.heatmap_mode, settings.heatmap_kind);
+    }
+}
+
+/// The components used to color the agents.
+type ColoredAgent<'a> = (
+    Entity,
+    &'a Transform,
+    &'a AgentBehaviour,
+    &'a Velocity,
+    &'a Sight,
+    Option<&'a FriendFoe>,
+);
+
+/// Set the material of the agents according to the [`ColorMode`].
+fn color_agents(
+    settings: Res<SimulationDebug>,
+    simulation_settings: Res<SimulationSettings>,
+    materials: Res<AgentMaterials>,
+    mut legend: ResMut<ColorLegend>,
+    agents: Query<ColoredAgent<'_>, With<Agent>>,
+    mut handles: Query<&mut Handle<ColorMaterial>, With<Agent>>,
+) {
+    // value on the gradient, before normalization
+    let mut values: Vec<(Entity, Option<f32>)> = Vec::new();
+    let mut colored: Vec<(Entity, Handle<ColorMaterial>)> = Vec::new();
+
+    match settings.color_mode {
+        ColorMode::Kind => {
+            for (entity, _, behaviour, ..) in agents.iter() {
+                colored.push((entity, materials.get(behaviour)));
+            }
+        }
+        ColorMode::Speed => {
+            for (entity, _, _, Velocity(velocity), ..) in agents.iter() {
+                values.push((entity, Some(velocity.length())));
+            }
+        }
+        ColorMode::FoeDistance => {
+            for (entity, transform, .., relation) in agents.iter() {
+                let foe = relation.and_then(|FriendFoe(_, foe)| agents.get(*foe).ok());
+                let distance =
+                    foe.map(|(_, foe, ..)| transform.translation.distance(foe.translation));
+                values.push((entity, distance));
+            }
+        }
+        ColorMode::FoeInDegree => {
+            let mut in_degree: HashMap<Entity, u32> = HashMap::new();
+            for (.., relation) in agents.iter() {
+                if let Some(FriendFoe(_, foe)) = relation {
+                    *in_degree.entry(*foe).or_default() += 1;
+                }
+            }
+            for (entity, ..) in agents.iter() {
+                let degree = in_degree.get(&entity).copied().unwrap_or_default();
+                values.push((entity, Some(degree as f32)));
+            }
+        }
+        ColorMode::Visibility => {
+            for (entity, _, _, _, sight, _) in agents.iter() {
+                let category = sight.friend as usize + 2 * sight.foe as usize;
+                colored.push((entity, materials.category(category)));
+            }
+        }
+        ColorMode::Cluster => {
+            let positions: Vec<_> = agents
+                .iter()
+                .map(|(entity, transform, behaviour, ..)| {
+                    (entity, transform.translation.xy(), behaviour)
+                })
+                .collect();
+            let clusters = clusters(&simulation_settings, settings.cluster_distance, &positions);
+            legend.clusters = clusters.len();
+            for (category, cluster) in clusters.into_iter().enumerate() {
+                for entity in cluster {
+                    colored.push((entity, materials.category(category)));
+                }
+            }
+            let clustered: HashSet<Entity> = colored.iter().map(|(entity, _)| *entity).collect();
+            for (entity, ..) in agents.iter() {
+                if !clustered.contains(&entity) {
+                    colored.push((entity, materials.neutral.clone()));
+                }
+            }
+        }
+    }
+
+    if !values.is_empty() {
+        legend.max = values
+            .iter()
+            .filter_map(|(_, value)| *value)
+            .fold(0.0, f32::max);
+        for (entity, value) in values {
+            let material = match value {
+                Some(value) if legend.max > 0.0 => materials.gradient(value / legend.max),
+                Some(_) => materials.gradient(0.0),
+                None => materials.neutral.clone(),
+            };
+            colored.push((entity, material));
+        }
+    }
+
+    for (entity, material) in colored {
+        if let Ok(mut handle) = handles.get_mut(entity) {
+            if *handle != material {
+                *handle = material;
+            }
+        }
+    }
+}
+
+/// Group the agents closer than `distance` from one another, directly or through other agents.
+/// The groups of at least two agents are returned, the largest first.
+fn clusters(
+    simulation_settings: &SimulationSettings,
+    distance: f32,
+    agents: &[(Entity, Vec2, &AgentBehaviour)],
+) -> Vec<Vec<Entity>> {
+    let mut grid = SpatialGrid::default();
+    fill_grid(&mut grid, simulation_settings, agents.iter().copied());
+
+    // flood fill from each agent not yet in a cluster
+    let mut clusters: Vec<Vec<Entity>> = Vec::new();
+    let mut visited: HashSet<Entity> = HashSet::new();
+    for &(entity, position, _) in agents {
+        if !visited.insert(entity) {
+            continue;
+        }
+
+        let mut cluster = vec![(entity, position)];
+        let mut index = 0;
+        while index < cluster.len() {
+            for (other, other_position, _) in grid.within(cluster[index].1, distance) {
+                if visited.insert(other) {
+                    cluster.push((other, other_position));
+                }
+            }
+            index += 1;
+        }
+        if cluster.len() > 1 {
+            clusters.push(cluster.into_iter().map(|(entity, _)| entity).collect());
+        }
+    }
+
+    clusters.sort_by_key(|cluster| std::cmp::Reverse(cluster.len()));
+    clusters
+}
+
+fn compute_stats(
+    time: Res<Time>,
+    mut stats: ResMut<SimStats>,
+    simulation_settings: Res<SimulationSettings>,
+    agents: Query<(Entity, &Transform, &AgentBehaviour), With<Agent>>,
+    abilities: Query<(&AgentBehaviour, &Speed, &VisionRange), With<Agent>>,
+) {
     let mut agent_count: u32 = 0;
 
     let center_of_mass = {
         let mut sum = Vec2::ZERO;
-        for transform in agents.iter() {
+        for (_, transform, _) in agents.iter() {
             sum += transform.translation.xy();
             agent_count += 1;
         }
@@ -449,12 +2774,121 @@ fn compute_stats(mut stats: ResMut<SimStats>, agents: Query<&Transform, With<Age
 
     let deviation = {
         let mut sum: f32 = 0.0;
-        for transform in agents.iter() {
+        for (_, transform, _) in agents.iter() {
             sum += (transform.translation.xy() - center_of_mass).length();
         }
         sum / agent_count as f32
     };
 
+    let overlap = {
+        let mut grid = SpatialGrid::default();
+        fill_grid(
+            &mut grid,
+            &simulation_settings,
+            agents.iter().map(|(entity, transform, behaviour)| {
+                (entity, transform.translation.xy(), behaviour)
+            }),
+        );
+
+        let mut overlapping: u32 = 0;
+        for (entity, transform, behaviour) in agents.iter() {
+            let position = transform.translation.xy();
+            let radius = simulation_settings.kind(behaviour).radius;
+            if grid.within(position, radius + grid.max_radius()).any(
+                |(e, other_pos, other_radius)| {
+                    e != entity && position.distance(other_pos) < radius + other_radius
+                },
+            ) {
+                overlapping += 1;
+            }
+        }
+        overlapping as f32 / agent_count as f32
+    };
+
+    // exponential moving average of the deviation
+    const DEVIATION_AVERAGE_TIME: f32 = 5.0;
+    let alpha = (time.delta_seconds() / DEVIATION_AVERAGE_TIME).min(1.0);
+    stats.mean_deviation += (deviation - stats.mean_deviation) * alpha;
+
     stats.center_of_mass = center_of_mass;
     stats.deviation = deviation;
+    stats.overlap = overlap;
+
+    // convex hulls
+    let positions = |kind: Option<AgentBehaviour>| {
+        agents
+            .iter()
+            .filter(|(_, _, behaviour)| kind.is_none() || kind == Some(**behaviour))
+            .map(|(_, transform, _)| transform.translation.xy())
+            .collect()
+    };
+    stats.heroe_hull = Hull::new(positions(Some(AgentBehaviour::Heroe)));
+    stats.coward_hull = Hull::new(positions(Some(AgentBehaviour::Coward)));
+    stats.hull = Hull::new(positions(None));
+
+    // mean of the speed and the vision, for each kind
+    let mut heroes = (0.0, 0.0, 0);
+    let mut cowards = (0.0, 0.0, 0);
+    for (behaviour, Speed(speed), VisionRange(vision)) in abilities.iter() {
+        let (speed_sum, vision_sum, count) = match behaviour {
+            AgentBehaviour::Heroe => &mut heroes,
+            AgentBehaviour::Coward => &mut cowards,
+        };
+        *speed_sum += speed;
+        *vision_sum += vision;
+        *count += 1;
+    }
+    let mean = |sum: f32, count: u32| if count == 0 { 0.0 } else { sum / count as f32 };
+    stats.mean_speed = (mean(heroes.0, heroes.2), mean(cowards.0, cowards.2));
+    stats.mean_vision = (mean(heroes.1, heroes.2), mean(cowards.1, cowards.2));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uniform_samples_stay_in_bounds() {
+        let mut rng = Pcg32::seed_from_u64(0);
+        let distribution = ParamDistribution::Uniform(0.5, 1.5);
+        for _ in 0..10_000 {
+            let value = distribution.sample(&mut rng);
+            assert!((0.5..1.5).contains(&value), "{} out of bounds", value);
+        }
+
+        // empty or reversed bounds give the min, negative values are clamped
+        assert_eq!(ParamDistribution::Uniform(2.0, 2.0).sample(&mut rng), 2.0);
+        assert_eq!(ParamDistribution::Uniform(3.0, 1.0).sample(&mut rng), 3.0);
+        for _ in 0..100 {
+            let value = ParamDistribution::Uniform(-1.0, 1.0).sample(&mut rng);
+            assert!((0.0..1.0).contains(&value));
+        }
+    }
+
+    #[test]
+    fn normal_samples() {
+        let mut rng = Pcg32::seed_from_u64(0);
+        let distribution = ParamDistribution::Normal {
+            mean: 1.5,
+            std_dev: 0.0,
+        };
+        for _ in 0..100 {
+            assert_eq!(distribution.sample(&mut rng), 1.5);
+        }
+
+        let distribution = ParamDistribution::Normal {
+            mean: 10.0,
+            std_dev: 2.0,
+        };
+        let n = 20_000;
+        let values: Vec<f32> = (0..n).map(|_| distribution.sample(&mut rng)).collect();
+        let mean = values.iter().sum::<f32>() / n as f32;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / n as f32;
+        assert!((mean - 10.0).abs() < 0.1, "mean {}", mean);
+        assert!(
+            (variance.sqrt() - 2.0).abs() < 0.1,
+            "std dev {}",
+            variance.sqrt()
+        );
+    }
 }
@@ -0,0 +1,344 @@
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+use crate::utils::*;
+
+/// The entities of a cell of the grid, with their position and radius.
+type Cell = Vec<(Entity, Vec2, f32)>;
+
+// ===== resources =====
+
+/// Uniform grid that index entities by their position.
+/// Used to avoid iterating over every agent for spatial queries.
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Cell>,
+    /// Radius of the largest entity.
+    max_radius: f32,
+    /// Smallest and largest cell coordinates containing an entity.
+    bounds: Option<((i32, i32), (i32, i32))>,
+}
+
+impl Default for SpatialGrid {
+    fn default() -> Self {
+        Self {
+            cell_size: 16.0,
+            cells: HashMap::new(),
+            max_radius: 0.0,
+            bounds: None,
+        }
+    }
+}
+
+impl SpatialGrid {
+    /// Remove all the entities from the grid and change the size of its cells.
+    pub fn clear(&mut self, cell_size: f32) {
+        self.cell_size = cell_size.max(1.0);
+        self.cells.clear();
+        self.max_radius = 0.0;
+        self.bounds = None;
+    }
+
+    /// Add an entity of the given radius at the given position.
+    pub fn insert(&mut self, entity: Entity, position: Vec2, radius: f32) {
+        let cell = (self.cell(position.x), self.cell(position.y));
+        self.cells
+            .entry(cell)
+            .or_default()
+            .push((entity, position, radius));
+        self.max_radius = self.max_radius.max(radius);
+        self.bounds = Some(match self.bounds {
+            None => (cell, cell),
+            Some((min, max)) => (
+                (min.0.min(cell.0), min.1.min(cell.1)),
+                (max.0.max(cell.0), max.1.max(cell.1)),
+            ),
+        });
+    }
+
+    /// Move an entity already in the grid from `from` to `to`.
+    pub fn move_entity(&mut self, entity: Entity, from: Vec2, to: Vec2) {
+        let cell = (self.cell(from.x), self.cell(from.y));
+        let radius = self.cells.get_mut(&cell).and_then(|entities| {
+            let index = entities.iter().position(|(e, ..)| *e == entity)?;
+            Some(entities.swap_remove(index).2)
+        });
+        if let Some(radius) = radius {
+            self.insert(entity, to, radius);
+        }
+    }
+
+    fn cell(&self, value: f32) -> i32 {
+        (value / self.cell_size).floor() as i32
+    }
+
+    /// Radius of the largest entity in the grid.
+    pub fn max_radius(&self) -> f32 {
+        self.max_radius
+    }
+
+    /// Iterate over the entities whose center is within `distance` of `position`,
+    /// with their position and radius.
+    pub fn within(
+        &self,
+        position: Vec2,
+        distance: f32,
+    ) -> impl Iterator<Item = (Entity, Vec2, f32)> + '_ {
+        let (x0, x1) = (
+            self.cell(position.x - distance),
+            self.cell(position.x + distance),
+        );
+        let (y0, y1) = (
+            self.cell(position.y - distance),
+            self.cell(position.y + distance),
+        );
+
+        (x0..=x1)
+            .flat_map(move |x| (y0..=y1).map(move |y| (x, y)))
+            .filter_map(move |cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+            .filter(move |(_, other, _)| position.distance(*other) <= distance)
+    }
+
+    /// Find the closest entity accepted by `filter` within `max_distance` of `position`.
+    ///
+    /// The cells are visited by rings of growing size around `position`,
+    /// and the search stop as soon as no closer entity can be found.
+    pub fn nearest(
+        &self,
+        position: Vec2,
+        max_distance: f32,
+        mut filter: impl FnMut(Entity, Vec2) -> bool,
+    ) -> Option<(Entity, Vec2)> {
+        let ((min_x, min_y), (max_x, max_y)) = self.bounds?;
+        let (cx, cy) = (self.cell(position.x), self.cell(position.y));
+
+        // no need to go further than the cells containing entities
+        let max_ring = (cx - min_x)
+            .max(max_x - cx)
+            .max(cy - min_y)
+            .max(max_y - cy)
+            .min(((max_distance / self.cell_size).ceil() + 1.0).min(i32::MAX as f32) as i32);
+
+        let mut best: Option<(Entity, Vec2, f32)> = None;
+        for ring in 0..=max_ring.max(0) {
+            // entities in this ring are at least this far away
+            let ring_distance = (ring - 1).max(0) as f32 * self.cell_size;
+            if matches!(best, Some((_, _, d)) if d <= ring_distance) {
+                break;
+            }
+
+            for x in (cx - ring)..=(cx + ring) {
+                for y in (cy - ring)..=(cy + ring) {
+                    // only visit the border of the ring
+                    if (x - cx).abs() != ring && (y - cy).abs() != ring {
+                        continue;
+                    }
+                    for (entity, other, _) in self.cells.get(&(x, y)).into_iter().flatten() {
+                        let distance = position.distance(*other);
+                        let closer = match best {
+                            Some((_, _, d)) => distance < d,
+                            None => true,
+                        };
+                        if distance <= max_distance && closer && filter(*entity, *other) {
+                            best = Some((*entity, *other, distance));
+                        }
+                    }
+                }
+            }
+        }
+
+        best.map(|(entity, other, _)| (entity, other))
+    }
+
+    /// Check if an entity accepted by `filter` intersect the segment `[a, b]`.
+    ///
+    /// Only the cells crossed by the segment (enlarged by the largest radius) are visited.
+    pub fn any_on_segment(&self, a: Vec2, b: Vec2, mut filter: impl FnMut(Entity) -> bool) -> bool {
+        let radius = self.max_radius;
+        let (seg_min_y, seg_max_y) = (a.y.min(b.y), a.y.max(b.y));
+
+        for cy in self.cell(seg_min_y - radius)..=self.cell(seg_max_y + radius) {
+            // part of the segment that cross this row of cells
+            let y0 = (cy as f32 * self.cell_size - radius)
+                .max(seg_min_y)
+                .min(seg_max_y);
+            let y1 = ((cy + 1) as f32 * self.cell_size + radius)
+                .min(seg_max_y)
+                .max(seg_min_y);
+            let (x0, x1) = if (b.y - a.y).abs() < f32::EPSILON {
+                (a.x.min(b.x), a.x.max(b.x))
+            } else {
+                let xa = a.x + (b.x - a.x) * (y0 - a.y) / (b.y - a.y);
+                let xb = a.x + (b.x - a.x) * (y1 - a.y) / (b.y - a.y);
+                (xa.min(xb), xa.max(xb))
+            };
+
+            for cx in self.cell(x0 - radius)..=self.cell(x1 + radius) {
+                if let Some(entities) = self.cells.get(&(cx, cy)) {
+                    for (entity, position, radius) in entities {
+                        if distance_to_segment(*position, a, b) < *radius && filter(*entity) {
+                            return true;
+                        }
+                    }
+                }
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::prelude::*;
+    use rand_pcg::Pcg32;
+
+    const CELL_SIZE: f32 = 10.0;
+
+    /// Random entities, with some of them exactly on the borders of the cells.
+    fn random_entities(rng: &mut Pcg32) -> Vec<(Entity, Vec2, f32)> {
+        (0..300)
+            .map(|i| {
+                let position = if i % 5 == 0 {
+                    Vec2::new(
+                        rng.gen_range(-5..=5) as f32 * CELL_SIZE,
+                        rng.gen_range(-5..=5) as f32 * CELL_SIZE,
+                    )
+                } else {
+                    Vec2::new(rng.gen_range(-50.0..50.0), rng.gen_range(-50.0..50.0))
+                };
+                (Entity::new(i), position, rng.gen_range(0.5..3.0))
+            })
+            .collect()
+    }
+
+    fn grid_of(entities: &[(Entity, Vec2, f32)]) -> SpatialGrid {
+        let mut grid = SpatialGrid::default();
+        grid.clear(CELL_SIZE);
+        for (entity, position, radius) in entities {
+            grid.insert(*entity, *position, *radius);
+        }
+        grid
+    }
+
+    fn random_point(rng: &mut Pcg32) -> Vec2 {
+        if rng.gen_bool(0.25) {
+            Vec2::new(
+                rng.gen_range(-6..=6) as f32 * CELL_SIZE,
+                rng.gen_range(-6..=6) as f32 * CELL_SIZE,
+            )
+        } else {
+            Vec2::new(rng.gen_range(-60.0..60.0), rng.gen_range(-60.0..60.0))
+        }
+    }
+
+    #[test]
+    fn nearest_matches_brute_force() {
+        let mut rng = Pcg32::seed_from_u64(1);
+        let entities = random_entities(&mut rng);
+        let grid = grid_of(&entities);
+
+        for _ in 0..500 {
+            let position = random_point(&mut rng);
+            let max_distance = rng.gen_range(1.0..80.0);
+            // skip the entities with an odd index to check the filter
+            let accepted = |entity: Entity| entity.id() % 2 != 1;
+
+            let expected = entities
+                .iter()
+                .filter(|(entity, other, _)| {
+                    accepted(*entity) && position.distance(*other) <= max_distance
+                })
+                .map(|(_, other, _)| position.distance(*other))
+                .min_by(|a, b| a.total_cmp(b));
+            let found = grid.nearest(position, max_distance, |entity, _| accepted(entity));
+
+            // several entities can be at the same distance, so compare the distances
+            match (expected, found) {
+                (None, None) => {}
+                (Some(distance), Some((entity, other))) => {
+                    assert!(accepted(entity));
+                    assert_eq!(position.distance(other), distance);
+                }
+                _ => panic!("nearest({}, {}) = {:?}", position, max_distance, found),
+            }
+        }
+    }
+
+    #[test]
+    fn any_on_segment_matches_brute_force() {
+        let mut rng = Pcg32::seed_from_u64(2);
+        let entities = random_entities(&mut rng);
+        let grid = grid_of(&entities);
+
+        let mut segments: Vec<(Vec2, Vec2)> = (0..500)
+            .map(|_| (random_point(&mut rng), random_point(&mut rng)))
+            .collect();
+        // segments along the lines of the grid
+        for i in -5..=5 {
+            let line = i as f32 * CELL_SIZE;
+            segments.push((Vec2::new(-55.0, line), Vec2::new(55.0, line)));
+            segments.push((Vec2::new(line, 55.0), Vec2::new(line, -55.0)));
+            segments.push((Vec2::new(line, line), Vec2::new(line + 3.0, line)));
+        }
+
+        for (a, b) in segments {
+            for parity in 0..2 {
+                let accepted = |entity: Entity| entity.id() % 2 == parity;
+                let expected = entities.iter().any(|(entity, position, radius)| {
+                    accepted(*entity) && distance_to_segment(*position, a, b) < *radius
+                });
+                assert_eq!(
+                    grid.any_on_segment(a, b, accepted),
+                    expected,
+                    "any_on_segment({}, {})",
+                    a,
+                    b
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn move_entity_updates_the_queries() {
+        let mut rng = Pcg32::seed_from_u64(3);
+        let mut entities = random_entities(&mut rng);
+        let mut grid = grid_of(&entities);
+
+        for (entity, position, _) in entities.iter_mut().step_by(2) {
+            let to = random_point(&mut rng);
+            grid.move_entity(*entity, *position, to);
+            *position = to;
+        }
+        // moving an entity that is not at `from` does nothing
+        grid.move_entity(Entity::new(1000), Vec2::ZERO, Vec2::ONE);
+
+        for _ in 0..200 {
+            let position = random_point(&mut rng);
+            let distance = rng.gen_range(0.0..30.0);
+
+            let mut expected: Vec<_> = entities
+                .iter()
+                .filter(|(_, other, _)| position.distance(*other) <= distance)
+                .map(|(entity, ..)| *entity)
+                .collect();
+            let mut found: Vec<_> = grid
+                .within(position, distance)
+                .map(|(entity, ..)| entity)
+                .collect();
+            expected.sort();
+            found.sort();
+            assert_eq!(found, expected);
+
+            let expected = entities
+                .iter()
+                .map(|(_, other, _)| position.distance(*other))
+                .min_by(|a, b| a.total_cmp(b));
+            let found = grid.nearest(position, f32::INFINITY, |_, _| true);
+            assert_eq!(found.map(|(_, other)| position.distance(other)), expected);
+        }
+    }
+}
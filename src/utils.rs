@@ -1,9 +1,19 @@
 use bevy::prelude::*;
 use bevy_prototype_debug_lines::DebugLines;
+use rand::Rng;
 
 pub trait DebugLinesExt {
     fn arrow_colored(&mut self, start: Vec3, end: Vec3, duration: f32, color: Color);
     fn circle_colored(&mut self, center: Vec3, radius: f32, duration: f32, color: Color);
+    fn arc_colored(
+        &mut self,
+        center: Vec3,
+        radius: f32,
+        angles: (f32, f32),
+        duration: f32,
+        color: Color,
+    );
+    fn cross_colored(&mut self, center: Vec3, size: f32, duration: f32, color: Color);
 }
 
 impl DebugLinesExt for DebugLines {
@@ -48,4 +58,177 @@ impl DebugLinesExt for DebugLines {
             self.line_colored(a, b, duration, color);
         }
     }
+    fn arc_colored(
+        &mut self,
+        center: Vec3,
+        radius: f32,
+        (start, end): (f32, f32),
+        duration: f32,
+        color: Color,
+    ) {
+        const SEGMENTS: usize = 20;
+
+        let p = radius * Vec3::X;
+        let step = (end - start) / SEGMENTS as f32;
+        for i in 0..SEGMENTS {
+            let a = center + Quat::from_rotation_z(start + i as f32 * step) * p;
+            let b = center + Quat::from_rotation_z(start + (i + 1) as f32 * step) * p;
+            self.line_colored(a, b, duration, color);
+        }
+    }
+    fn cross_colored(&mut self, center: Vec3, size: f32, duration: f32, color: Color) {
+        let a = Vec3::new(size, size, 0.0);
+        let b = Vec3::new(size, -size, 0.0);
+        self.line_colored(center - a, center + a, duration, color);
+        self.line_colored(center - b, center + b, duration, color);
+    }
+}
+
+/// Compute the distance between `point` and the segment `[a, b]`.
+pub fn distance_to_segment(point: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    let length_squared = ab.length_squared();
+    if length_squared == 0.0 {
+        return point.distance(a);
+    }
+    let t = ((point - a).dot(ab) / length_squared).clamp(0.0, 1.0);
+    point.distance(a + t * ab)
+}
+
+/// Sample a value from the standard normal distribution, using the Box-Muller transform.
+pub fn standard_normal(rng: &mut impl Rng) -> f32 {
+    let u1: f32 = rng.gen_range(f32::EPSILON..1.0);
+    let u2: f32 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (std::f32::consts::TAU * u2).cos()
+}
+
+/// Compute the convex hull of `points`, counterclockwise, using the monotone chain algorithm.
+/// Collinear points on the edges are dropped.
+pub fn convex_hull(mut points: Vec<Vec2>) -> Vec<Vec2> {
+    points.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+
+    // whether `a`, `b`, `c` turn counterclockwise
+    let ccw = |a: Vec2, b: Vec2, c: Vec2| (b - a).perp_dot(c - a) > 0.0;
+
+    let mut hull: Vec<Vec2> = Vec::with_capacity(2 * points.len());
+    // lower hull, from left to right
+    for &point in &points {
+        while hull.len() >= 2 && !ccw(hull[hull.len() - 2], hull[hull.len() - 1], point) {
+            hull.pop();
+        }
+        hull.push(point);
+    }
+    // upper hull, from right to left
+    let lower_len = hull.len();
+    for &point in points.iter().rev().skip(1) {
+        while hull.len() > lower_len && !ccw(hull[hull.len() - 2], hull[hull.len() - 1], point) {
+            hull.pop();
+        }
+        hull.push(point);
+    }
+    // the first point is repeated at the end
+    hull.pop();
+    hull
+}
+
+/// Area of a simple polygon, using the shoelace formula.
+pub fn polygon_area(points: &[Vec2]) -> f32 {
+    let sum: f32 = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| a.perp_dot(*b))
+        .sum();
+    sum.abs() / 2.0
+}
+
+/// Perimeter of a closed polygon.
+pub fn polygon_perimeter(points: &[Vec2]) -> f32 {
+    if points.len() < 2 {
+        return 0.0;
+    }
+    points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| a.distance(*b))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convex_hull_of_a_square() {
+        let points = vec![
+            Vec2::new(0.0, 0.0),
+            Vec2::new(2.0, 0.0),
+            Vec2::new(2.0, 2.0),
+            Vec2::new(0.0, 2.0),
+            // inside and on an edge
+            Vec2::new(1.0, 1.0),
+            Vec2::new(1.0, 0.0),
+        ];
+        let hull = convex_hull(points);
+        assert_eq!(
+            hull,
+            vec![
+                Vec2::new(0.0, 0.0),
+                Vec2::new(2.0, 0.0),
+                Vec2::new(2.0, 2.0),
+                Vec2::new(0.0, 2.0),
+            ]
+        );
+        assert_eq!(polygon_area(&hull), 4.0);
+        assert_eq!(polygon_perimeter(&hull), 8.0);
+    }
+
+    #[test]
+    fn convex_hull_of_collinear_points() {
+        let points = (0..5)
+            .map(|i| Vec2::new(i as f32, 2.0 * i as f32))
+            .collect();
+        let hull = convex_hull(points);
+        assert_eq!(hull, vec![Vec2::new(0.0, 0.0), Vec2::new(4.0, 8.0)]);
+        assert_eq!(polygon_area(&hull), 0.0);
+        // the segment is walked both ways
+        assert!((polygon_perimeter(&hull) - 2.0 * 80f32.sqrt()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn convex_hull_of_duplicate_points() {
+        let triangle = [
+            Vec2::new(0.0, 0.0),
+            Vec2::new(3.0, 0.0),
+            Vec2::new(0.0, 4.0),
+        ];
+        let points = triangle.iter().chain(&triangle).copied().collect();
+        let hull = convex_hull(points);
+        assert_eq!(hull.len(), 3);
+        assert_eq!(polygon_area(&hull), 6.0);
+        assert_eq!(polygon_perimeter(&hull), 12.0);
+
+        let hull = convex_hull(vec![Vec2::ONE; 4]);
+        assert_eq!(hull, vec![Vec2::ONE]);
+    }
+
+    #[test]
+    fn convex_hull_of_few_points() {
+        assert!(convex_hull(Vec::new()).is_empty());
+        assert_eq!(polygon_area(&[]), 0.0);
+        assert_eq!(polygon_perimeter(&[]), 0.0);
+
+        let hull = convex_hull(vec![Vec2::new(1.0, 2.0)]);
+        assert_eq!(hull, vec![Vec2::new(1.0, 2.0)]);
+        assert_eq!(polygon_area(&hull), 0.0);
+        assert_eq!(polygon_perimeter(&hull), 0.0);
+
+        let hull = convex_hull(vec![Vec2::new(3.0, 0.0), Vec2::new(0.0, 4.0)]);
+        assert_eq!(hull, vec![Vec2::new(0.0, 4.0), Vec2::new(3.0, 0.0)]);
+        assert_eq!(polygon_area(&hull), 0.0);
+        assert_eq!(polygon_perimeter(&hull), 10.0);
+    }
 }
@@ -3,17 +3,221 @@ use bevy::prelude::*;
 // ===== components =====
 
 /// The velocity of an entity.
-/// The position of the entity is updated based on the value of the velocity
-/// and the value of the [`Speed`] component.
 #[derive(Default)]
 pub struct Velocity(pub Vec3);
 
+/// The direction an entity is facing.
+/// Derived from its [`Velocity`], kept unchanged while the entity doesn't move.
+pub struct Heading(pub Vec3);
+
+impl Default for Heading {
+    fn default() -> Self {
+        Self(Vec3::X)
+    }
+}
+
+/// Numerical method used to integrate the motion of the entities.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Integrator {
+    /// Move with the velocity at the beginning of the step, then update the velocity.
+    ExplicitEuler,
+    /// Update the velocity, then move with the new velocity.
+    SemiImplicitEuler,
+    /// Velocity Verlet, average of the accelerations at both ends of the step.
+    Verlet,
+    /// Classic 4th order Runge-Kutta.
+    RungeKutta4,
+}
+
+impl Integrator {
+    /// The method actually used by [`Integrator::step_position`]:
+    /// without acceleration, both Euler methods are the same.
+    pub fn first_order(self) -> Self {
+        match self {
+            Integrator::SemiImplicitEuler => Integrator::ExplicitEuler,
+            integrator => integrator,
+        }
+    }
+
+    /// Advance `position` by `delta` seconds, when the velocity only depends on the position.
+    /// Both Euler methods are the explicit one and [`Integrator::Verlet`] becomes Heun's method
+    /// (the trapezoidal rule).
+    pub fn step_position(
+        &self,
+        position: Vec3,
+        delta: f32,
+        velocity: impl Fn(Vec3) -> Vec3,
+    ) -> Vec3 {
+        match self {
+            Integrator::ExplicitEuler | Integrator::SemiImplicitEuler => {
+                position + velocity(position) * delta
+            }
+            // Heun's method, the first order counterpart of velocity Verlet
+            Integrator::Verlet => {
+                let v0 = velocity(position);
+                let v1 = velocity(position + v0 * delta);
+                position + (v0 + v1) * delta / 2.0
+            }
+            Integrator::RungeKutta4 => {
+                let k1 = velocity(position);
+                let k2 = velocity(position + k1 * delta / 2.0);
+                let k3 = velocity(position + k2 * delta / 2.0);
+                let k4 = velocity(position + k3 * delta);
+                position + (k1 + 2.0 * k2 + 2.0 * k3 + k4) * delta / 6.0
+            }
+        }
+    }
+
+    /// Advance `position` and `velocity` by `delta` seconds,
+    /// given the `acceleration` at a position and a velocity.
+    pub fn step(
+        &self,
+        position: Vec3,
+        velocity: Vec3,
+        delta: f32,
+        acceleration: impl Fn(Vec3, Vec3) -> Vec3,
+    ) -> (Vec3, Vec3) {
+        match self {
+            Integrator::ExplicitEuler => (
+                position + velocity * delta,
+                velocity + acceleration(position, velocity) * delta,
+            ),
+            Integrator::SemiImplicitEuler => {
+                let velocity = velocity + acceleration(position, velocity) * delta;
+                (position + velocity * delta, velocity)
+            }
+            Integrator::Verlet => {
+                let a0 = acceleration(position, velocity);
+                let position = position + velocity * delta + a0 * delta * delta / 2.0;
+                // the acceleration depends on the velocity, predicted with Euler
+                let a1 = acceleration(position, velocity + a0 * delta);
+                (position, velocity + (a0 + a1) * delta / 2.0)
+            }
+            Integrator::RungeKutta4 => {
+                let (x1, v1) = (position, velocity);
+                let a1 = acceleration(x1, v1);
+                let (x2, v2) = (position + v1 * delta / 2.0, velocity + a1 * delta / 2.0);
+                let a2 = acceleration(x2, v2);
+                let (x3, v3) = (position + v2 * delta / 2.0, velocity + a2 * delta / 2.0);
+                let a3 = acceleration(x3, v3);
+                let (x4, v4) = (position + v3 * delta, velocity + a3 * delta);
+                let a4 = acceleration(x4, v4);
+                (
+                    position + (v1 + 2.0 * v2 + 2.0 * v3 + v4) * delta / 6.0,
+                    velocity + (a1 + 2.0 * a2 + 2.0 * a3 + a4) * delta / 6.0,
+                )
+            }
+        }
+    }
+}
+
 // ===== systems =====
 
-/// Update the position of entities based on their [`Velocity`] and [`Speed`] components.
-pub fn update_velocities(time: Res<Time>, mut q: Query<(&mut Transform, &Velocity)>) {
-    for (mut transform, Velocity(velocity)) in q.iter_mut() {
-        // calculate the new position of the agent
-        transform.translation += *velocity * time.delta_seconds();
+/// Update the [`Heading`] of entities based on their [`Velocity`].
+pub fn update_headings(mut q: Query<(&mut Heading, &Velocity)>) {
+    for (mut heading, Velocity(velocity)) in q.iter_mut() {
+        if *velocity != Vec3::ZERO {
+            heading.0 = velocity.normalize();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTEGRATORS: [Integrator; 4] = [
+        Integrator::ExplicitEuler,
+        Integrator::SemiImplicitEuler,
+        Integrator::Verlet,
+        Integrator::RungeKutta4,
+    ];
+
+    /// Integrate for `duration` seconds in `steps` steps under a constant acceleration.
+    fn constant_acceleration(integrator: Integrator, duration: f32, steps: usize) -> (Vec3, Vec3) {
+        let delta = duration / steps as f32;
+        let acceleration = Vec3::new(0.0, -9.81, 0.0);
+        (0..steps).fold(
+            (Vec3::new(1.0, 2.0, 0.0), Vec3::new(3.0, 4.0, 0.0)),
+            |(position, velocity), _| {
+                integrator.step(position, velocity, delta, |_, _| acceleration)
+            },
+        )
+    }
+
+    #[test]
+    fn step_matches_constant_acceleration() {
+        let (x0, v0, a, t) = (
+            Vec3::new(1.0, 2.0, 0.0),
+            Vec3::new(3.0, 4.0, 0.0),
+            Vec3::new(0.0, -9.81, 0.0),
+            2.0,
+        );
+        let expected_position = x0 + v0 * t + a * t * t / 2.0;
+        let expected_velocity = v0 + a * t;
+
+        for integrator in INTEGRATORS {
+            let (position, velocity) = constant_acceleration(integrator, t, 1000);
+            assert!(
+                position.distance(expected_position) < 0.02,
+                "{:?}: {} instead of {}",
+                integrator,
+                position,
+                expected_position
+            );
+            assert!(
+                velocity.distance(expected_velocity) < 1e-3,
+                "{:?}",
+                integrator
+            );
+        }
+
+        // the second order methods are exact, even in a single step
+        for integrator in [Integrator::Verlet, Integrator::RungeKutta4] {
+            let (position, velocity) = constant_acceleration(integrator, t, 1);
+            assert!(
+                position.distance(expected_position) < 1e-4,
+                "{:?}",
+                integrator
+            );
+            assert!(
+                velocity.distance(expected_velocity) < 1e-4,
+                "{:?}",
+                integrator
+            );
+        }
+    }
+
+    #[test]
+    fn euler_methods_differ_with_acceleration() {
+        let (explicit, _) = constant_acceleration(Integrator::ExplicitEuler, 1.0, 10);
+        let (semi_implicit, _) = constant_acceleration(Integrator::SemiImplicitEuler, 1.0, 10);
+        assert!(explicit.distance(semi_implicit) > 0.1);
+    }
+
+    #[test]
+    fn step_position_matches_exponential_decay() {
+        // dx/dt = -x, so x(t) = x0 * exp(-t)
+        let (steps, duration) = (100, 1.0);
+        let delta = duration / steps as f32;
+        let expected = Vec3::X * (-duration).exp();
+
+        for (integrator, tolerance) in [
+            (Integrator::ExplicitEuler, 1e-2),
+            (Integrator::SemiImplicitEuler, 1e-2),
+            (Integrator::Verlet, 1e-4),
+            (Integrator::RungeKutta4, 1e-5),
+        ] {
+            let position = (0..steps).fold(Vec3::X, |position, _| {
+                integrator.step_position(position, delta, |p| -p)
+            });
+            assert!(
+                position.distance(expected) < tolerance,
+                "{:?}: {} instead of {}",
+                integrator,
+                position,
+                expected
+            );
+        }
     }
 }